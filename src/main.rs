@@ -1,8 +1,24 @@
+// Lints
+#![allow(clippy::assertions_on_constants)]
+#![allow(clippy::for_kv_map)]
+#![allow(clippy::needless_borrow)]
+#![allow(clippy::needless_late_init)]
+#![allow(clippy::needless_range_loop)]
+#![allow(clippy::needless_return)]
+#![allow(clippy::println_empty_string)]
+#![allow(clippy::question_mark)]
+#![allow(clippy::single_match)]
+#![allow(clippy::to_string_in_format_args)]
+#![allow(clippy::unnecessary_to_owned)]
+
 // Module
 mod vault;
 
 /// Main
 fn main()
 {
-	vault::run();
+	if !vault::run()
+	{
+		std::process::exit(1);
+	}
 }
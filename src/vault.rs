@@ -1,13 +1,18 @@
 // Mod
 mod args;
 mod config;
+mod journal;
+mod macros;
 mod rotate;
+mod size;
 mod state;
+mod step;
 mod task;
 mod time;
+mod trash;
 
 // Use
-use crate::vault::args::Args;
+use crate::vault::args::{Args, ArgsCommand, ArgsTrash};
 use crate::vault::config::Config;
 use crate::vault::task::Task;
 use crate::vault::time::Time;
@@ -21,7 +26,7 @@ fn help(a_long: bool)
 		match Args::cmd().print_long_help()
 		{
 			Ok(_) => (),
-			Err(_) => println!("Error: Failed to show long help text!"),
+			Err(_) => eprintln!("Error: Failed to show long help text!"),
 		}
 	}
 	else
@@ -29,7 +34,7 @@ fn help(a_long: bool)
 		match Args::cmd().print_help()
 		{
 			Ok(_) => (),
-			Err(_) => println!("Error: Failed to show short help text!"),
+			Err(_) => eprintln!("Error: Failed to show short help text!"),
 		}
 	}
 }
@@ -57,6 +62,12 @@ pub fn run() -> bool
 			None => return false,
 		};
 
+		// Allow mass rotate
+		l_cfg.allow_mass_rotate = l_args.allow_mass_rotate;
+
+		// Wait
+		l_cfg.wait = l_args.wait;
+
 		// Debug
 		l_cfg.debug = l_args.debug;
 		if l_args.debug
@@ -67,12 +78,64 @@ pub fn run() -> bool
 		// No name
 		if l_cfg.name.is_empty()
 		{
-			println!("Error: Configuration file '{}' has no name!", l_config.display());
+			eprintln!("Error: Configuration file '{}' has no name!", l_config.display());
 			return false;
 		}
 
+		// Command given
+		if let Some(l_command) = l_args.command
+		{
+			match l_command
+			{
+				// Pin
+				ArgsCommand::Pin { task, file, marker } =>
+				{
+					return Task::run_pin(&l_cfg, task.as_str(), file.as_str(), true, marker);
+				},
+
+				// Rotate
+				ArgsCommand::Rotate { task, plan, json } =>
+				{
+					if !json
+					{
+						println!("vault at {}", Time::to_string(&Time::now()));
+					}
+					let l_status = Task::run_rotate(&l_cfg, task.as_str(), plan, json);
+					if !json
+					{
+						println!("");
+					}
+					return l_status;
+				},
+
+				// Trash empty
+				ArgsCommand::Trash { command: ArgsTrash::Empty { task } } =>
+				{
+					return Task::run_trash(&l_cfg, task.as_str(), None);
+				},
+
+				// Trash restore
+				ArgsCommand::Trash { command: ArgsTrash::Restore { task, file } } =>
+				{
+					return Task::run_trash(&l_cfg, task.as_str(), Some(file.as_str()));
+				},
+
+				// Undo rotation
+				ArgsCommand::UndoRotation { task, run } =>
+				{
+					return Task::run_undo(&l_cfg, task.as_str(), run.as_str());
+				},
+
+				// Unpin
+				ArgsCommand::Unpin { task, file } =>
+				{
+					return Task::run_pin(&l_cfg, task.as_str(), file.as_str(), false, false);
+				},
+			}
+		}
+
 		// Task given
-		if let Some(l_task) = l_args.task
+		else if let Some(l_task) = l_args.task
 		{
 			println!("vault at {}", Time::to_string(&Time::now()));
 			let l_status = Task::run(&l_cfg, l_task.as_str());
@@ -83,7 +146,7 @@ pub fn run() -> bool
 		// No task given
 		else
 		{
-			println!("Error: No task specified!");
+			eprintln!("Error: No task specified!");
 			help(false);
 			return false;
 		}
@@ -92,7 +155,7 @@ pub fn run() -> bool
 	// No config given
 	else
 	{
-		println!("Error: No configuration file specified!");
+		eprintln!("Error: No configuration file specified!");
 		help(false);
 		return false;
 	}
@@ -108,11 +171,61 @@ pub const SAMPLE: &str = r#"############################################
 # Default: (empty)
 name = "test"
 
+# Timezone of this configuration.
+# Either an IANA name (e.g. "Europe/Berlin"), "local" for the zone of the
+# system or "UTC". Used for "{STAMP}" and for the boundaries of the
+# rotation buckets, so a "day" ends at midnight of this zone.
+# Outside of UTC, "%z" is appended to "{STAMP}" of new backups unless
+# "stamp_format" already contains an offset, so stamps stay unambiguous
+# across clock changes.
+# Default: "UTC"
+timezone = "UTC"
+
+# Variables of this configuration.
+# Values repeated across tasks can be defined once here and used as
+# "{var:NAME}" in "commands", "path", "rotate_command" and
+# "rotate_move_target" of every task.
+# A variable can contain macro keywords (See "commands")
+# and other variables, but must not reference itself, not even
+# through other variables.
+# Tasks can define variables of their own, which take precedence.
+# Default: (empty)
+[vars]
+host = "db01"
+source = "/var/lib/{var:host}"
+
 # A task in this configuration.
 # The task is named by the string after "task.", in this case "name_of_task".
 # The name can be anything, except being empty.
 [tasks.name_of_task]
 
+# Array of artifact groups.
+# A task can produce different kinds of backups,
+# which are rotated independently of each other.
+# Each artifact group has its own "file_pattern", "rotate", "rotate_glob"
+# and "rotate_source" settings (See below for a description).
+# An artifact group can also have a "name", which is used in messages.
+# With "verify = true", the artifact group is checked after the commands
+# (See "verify_max_deviation" and "verify_min_size").
+# An artifact group can also be given as a plain file pattern,
+# which uses the rotation settings of the task and is always verified.
+# If no artifact groups are given,
+# the settings of the task itself describe the only artifact group.
+# Example:
+# [[tasks.name_of_task.artifacts]]
+# file_pattern = "db-{STAMP}.sql.gz"
+# name = "db"
+# rotate = {keep_daily = 14}
+#
+# [[tasks.name_of_task.artifacts]]
+# file_pattern = "files-{STAMP}.tar.zst"
+# name = "files"
+# rotate = {keep_last = 3}
+#
+# Example: artifacts = ["{STAMP}.sql.gz"]
+# Default: []
+artifacts = []
+
 # Array of console commands.
 # In this setting you can specify, which commands have to be executed.
 # Usually you can specify backup commands here,
@@ -122,6 +235,21 @@ name = "test"
 # Each command can also contain special macro keywords,
 # that will be evaluated by Vault and replaced with dynamic data.
 # The following macro keywords exist:
+# {CONFIG} = Name of this configuration (See "name").
+#            Example: test
+# {DATE} = Current date of the exact moment in "timezone".
+#          Example: 2023-05-20
+# {ENV:NAME} = Value of the environment variable "NAME",
+#              empty if it is not set.
+#              Example: {ENV:HOME} = /home/cisco211
+# {HOST} = Host name of this machine.
+#          Example: backup01
+# {LAST} = Name of the newest backup in "path" before this run
+#          (See "file_pattern"), e.g. for "rsync --link-dest={LAST}".
+#          With artifacts, this is the newest backup of the first one,
+#          "{LAST:NAME}" is the newest backup of the artifact "NAME".
+#          Empty, if there is no backup yet.
+#          Example: 2023-05-20_04-55-11.007757100.tar
 # {NOW} = Current time and date of the exact moment.
 #         Do not use this keyword for filenames and directory names, ...,
 #         because some time has passed each time it is used (See "{STAMP}").
@@ -129,11 +257,84 @@ name = "test"
 #         Example: 2023-05-20_04-55-11.007757100
 # {PATH} = Configured directory of this task (See "path" for description).
 #          Example: /home/cisco211/vault/test/linux
+# {PREV} = Stamp of the newest backup of "{LAST}" (See "stamp_format").
+#          "{PREV:NAME}" is the stamp of "{LAST:NAME}".
+#          Empty, if there is no backup yet.
+#          Example: 2023-05-20_04-55-11.007757100
 # {STAMP} = Current time and date stamp of this task.
 #           The date and time will not change while the task runs.
 #           This is ideal for filenames and directory names.
-#           Format: %Y-%m-%d_%H-%M-%S%.9f
+#           Format: See "stamp_format".
 #           Example: 2023-05-20_06-19-41.386912900
+# {STAMP:FORMAT} = Same time and date as "{STAMP}",
+#                  but in another strftime format.
+#                  Example: {STAMP:%Y%m%d} = 20230520
+# {SEQ} = Number of this run of the task, counting up from 1.
+#         Example: 42
+# {TASK} = Name of this task.
+#          Example: name_of_task
+# {TIME} = Current time of the exact moment in "timezone".
+#          Example: 06-19-41
+# {USER} = Name of the user running Vault.
+#          Example: cisco211
+# Commands are not run by a shell, but split into arguments at their spaces,
+# including the spaces of variables and captures (e.g. "{var:NAME}").
+# The value of any other macro keyword always stays inside its argument,
+# even if it contains spaces (e.g. "{FILE}", "{PATH}" or "{LAST}").
+# Unknown macro keywords are an error.
+# For literal braces, write "{{" and "}}" (e.g. "awk '{{print $1}}'").
+# Instead of a string, a command can also be a table with the keys
+# "command" and "capture". With "capture", the trimmed standard output
+# of the command is not shown, but stored in a variable of that name for
+# the commands after it (See "vars"), e.g.:
+# commands = [
+#   {command = "snapshot create", capture = "snapshot_id"},
+#   "snapshot export {var:snapshot_id} {STAMP}.img",
+# ]
+# A capture name can consist of letters, digits, "_" and "-", but must
+# not be the name of a variable or a built-in macro keyword (e.g. "STAMP").
+# A table with a "type" other than "command" is a built-in step,
+# which Vault executes itself, the same way on every system.
+# With the type "archive", the "sources" are written into a tar archive
+# inside "path", with the type "zip" into a zip file.
+# With the type "snapshot", they are copied into a directory inside "path",
+# keeping permissions, modification times and symbolic links.
+# Files unchanged since the previous snapshot (same size, modification time
+# and permissions) are hard linked to it instead of copied again.
+# The previous snapshot is the newest directory matching "file".
+# Size limits ("max_total_size" and "moved_max_size") count hard linked
+# files once, for the newest snapshot sharing them. Verification compares
+# the full size of a snapshot with the full size of the previous one.
+# Relative sources are relative to "path".
+# A "path" inside a source is left out with all its backups, a source
+# at "path" itself leaves out the files of Vault (e.g. "state.toml").
+# The table has the following keys:
+# "compression" = "gzip", "xz" or "zstd" for "archive". Default: "zstd"
+#                 "deflate" or "zstd" for "zip". Default: "deflate"
+#                 Not used for "snapshot".
+# "exclude" = Globs of entries to leave out, e.g. ["*.log", "www/cache"].
+#             A glob matches the name inside the archive or its last part.
+#             Excluded directories are left out with all their content.
+# "file" = Name of the archive or snapshot, can contain macro keywords.
+#          A snapshot must contain "{STAMP}".
+#          Default: "{STAMP}.tar.gz", "{STAMP}.tar.xz", "{STAMP}.tar.zst",
+#                   "{STAMP}.zip" or "{STAMP}"
+# "include" = Globs of files to take, all files if empty.
+# "password" = Encrypts the files of a zip file with AES-256,
+#              can contain macro keywords, e.g. "{ENV:ZIP_PASSWORD}".
+#              Default: (empty) = Not encrypted
+# "sources" = Files and directories to archive, can contain macro keywords.
+#             Each is stored under its own name in the archive.
+# "symlinks" = "keep" archives symbolic links as links,
+#              "follow" archives what they point to,
+#              "skip" leaves them out. Default: "keep"
+# Make sure the archive matches "file_pattern",
+# so it takes part in rotation, e.g.:
+# commands = [
+#   {type = "archive", sources = ["/etc", "/srv/www"], exclude = ["*.log"]},
+#   {type = "zip", sources = ["/srv/www"], file = "{STAMP}.www.zip"},
+#   {type = "snapshot", sources = ["/home"], file = "home-{STAMP}"},
+# ]
 # Default: (empty)
 commands = ["touch {STAMP}.txt", "echo {STAMP}.txt"]
 
@@ -142,6 +343,20 @@ commands = ["touch {STAMP}.txt", "echo {STAMP}.txt"]
 # Default: false
 enabled = false
 
+# File pattern of the backups of this task.
+# Describes the names of the backups inside "path" for rotation.
+# Must contain "{STAMP}" exactly once,
+# which is parsed back into a time and date using "stamp_format".
+# "*" matches any text and "?" matches any single character.
+# Files not matching this pattern are never rotated.
+# Backups can also be directories (e.g. snapshots of "rsync ... {STAMP}/"),
+# which are rotated as a whole by every "rotate_strategy".
+# Vault's own entries ("state.toml", "state.lock", "journal.toml", "moved",
+# "*.keep" markers and unfinished "*.part" transfers) are never rotated.
+# Example: "db-{STAMP}.sql.gz"
+# Default: "{STAMP}*"
+file_pattern = "{STAMP}*"
+
 # Task execution interval in seconds.
 # With this setting you can specify, how long it takes,
 # until this task can be executed again.
@@ -151,9 +366,67 @@ enabled = false
 # For example,
 # the cronjob can run every 5 minutes while the task runs only once an hour.
 # That is, the task will run once an hour +/- 5 minutes.
+# Vault exits with status 1 if a task fails (e.g. a command or the
+# verification), skipped tasks do not count as failed.
 # Default: 0
 interval = 10
 
+# Maximum number of rotated backups.
+# Safety guard against mass rotation, e.g. after a wrong "stamp_format"
+# or a clock jump. Rotation aborts with an error, if a single run would
+# rotate more than this number of backups.
+# The command line argument "--allow-mass-rotate" overrides this guard.
+# 0 = No limit.
+# Default: 0
+max_rotate_count = 0
+
+# Maximum share of rotated backups.
+# Like "max_rotate_count", but as a share of the existing backups
+# between 0.0 and 1.0 (e.g. 0.5 = more than half of them).
+# 0.0 = No limit.
+# Default: 0.0
+max_rotate_fraction = 0.0
+
+# Maximum age of moved files.
+# Files this task moved into "rotate_move_target" older than this are
+# deleted after each rotation, the age being the time the file has been moved.
+# Only files recorded as moved there in the journal of the task are
+# touched, anything else in that directory is left alone.
+# Units: s, m, h, d, w and y (365 days), e.g. "90d".
+# The moved files can also be managed with
+# "vault -c <FILE> trash empty <TASK>" and
+# "vault -c <FILE> trash restore <TASK> <MOVED_FILE>".
+# Empty = No limit.
+# Default: ""
+moved_max_age = ""
+
+# Maximum total size of moved files.
+# If the files this task moved into "rotate_move_target" are larger than
+# this in total, the oldest moved ones are deleted after each rotation.
+# Units: B, KB, KiB, MB, MiB, GB, GiB, TB and TiB, e.g. "100GiB".
+# Empty = No limit.
+# Default: ""
+moved_max_size = ""
+
+# Lock policy.
+# Defines what happens, if this task is still locked by another process
+# (See "singleton").
+# The following policies are possible:
+# "fail" = The task fails with an error.
+# "skip" = The task is skipped.
+# "wait" = The task waits until the lock is released.
+#          It fails with an error, if "on_locked_timeout" elapses first.
+# The command line argument "--wait [SECONDS]" always forces "wait"
+# with the given timeout.
+# Default: "skip"
+on_locked = "skip"
+
+# Lock wait timeout in seconds.
+# Maximum time to wait for the lock, if "on_locked" is "wait".
+# 0 = Wait without timeout.
+# Default: 0
+on_locked_timeout = 0
+
 # The task execution order for this task.
 # When you run all tasks,
 # this setting allows you to determine the order of the tasks to be run.
@@ -167,26 +440,126 @@ order = 0
 # Vault operates in this directory,
 # so make sure your backups end up in this directory.
 # This setting also defines what is used for "{PATH}".
+# It can contain variables (See "vars") and the macro keywords not
+# changing from run to run ("{CONFIG}", "{ENV:NAME}", "{HOST}", "{TASK}"
+# and "{USER}"), which are evaluated once when the configuration is loaded.
 # Default: (leer)
 path = "/home/cisco211/vault/test/linux"
 
 # Use rotation.
-# Activates rotation individually for hourly, daily, monthly and yearly.
+# Activates rotation individually for hourly, daily, weekly, monthly and yearly.
 # This will handle (move/delete) the older backups,
-# except the last one in the time period and current hour/day/month/year.
+# except the last one in the time period and current hour/day/week/month/year.
+# Weeks are ISO weeks (Monday to Sunday).
 # Example: If daily is enabled,
 # all backups except today's will be reduced to the last backup of that day.
 # If multiple are enabled, hourly is always reduced first, then daily,
-# then monthly, then yearly.
-# Default: {daily = false, hourly = false, monthly = false, yearly = false}
+# then weekly, then monthly, then yearly.
+# Default: {daily = false, hourly = false, monthly = false, weekly = false,
+#          yearly = false}
+#
+# Retention by count.
+# In addition to the above, the number of backups can be limited with
+# "keep_last", "keep_hourly", "keep_daily", "keep_weekly", "keep_monthly"
+# and "keep_yearly".
+# "keep_last" keeps the given number of most recent backups.
+# The others keep the most recent backup of each of the given number of
+# most recent hours/days/ISO weeks/months/years.
+# A backup is kept, if at least one of these rules keeps it,
+# all other backups will be handled (moved/deleted).
+# Retention by count is applied after the rotations above.
+# 0 = Rule is disabled.
+# Example: {keep_last = 5, keep_daily = 14, keep_weekly = 8,
+#          keep_monthly = 12, keep_yearly = 5}
+# Default: {keep_daily = 0, keep_hourly = 0, keep_last = 0,
+#          keep_monthly = 0, keep_weekly = 0, keep_yearly = 0}
+#
+# Pruning limits.
+# Finally, the oldest backups are handled (moved/deleted) by age and size.
+# "max_age" = Maximum age of a backup.
+#             Units: s, m, h, d, w and y (365 days), e.g. "400d" or "1y2w".
+# "max_total_size" = Maximum total size of all backups.
+#                    Units: B, KB, KiB, MB, MiB, GB, GiB, TB and TiB,
+#                    e.g. "500GiB".
+#                    Files hard linked between backups count once.
+# "min_keep" = Number of most recent backups,
+#              that are always kept regardless of these limits.
+#              The default 1 keeps a new backup, even if it alone
+#              exceeds "max_total_size".
+#              0 = Even the most recent backup may be handled.
+# Empty = Limit is disabled.
+# Example: {max_age = "400d", max_total_size = "500GiB", min_keep = 3}
+# Default: {max_age = "", max_total_size = "", min_keep = 1}
+#
+# Pinned backups.
+# Backups pinned with "vault -c <FILE> pin <TASK> <BACKUP>"
+# or by a "<BACKUP>.keep" marker file are never handled (moved/deleted),
+# but still count for all of the above.
 rotate = {daily = true, hourly = true, monthly = true, yearly = true}
 
+# Rotate command.
+# Command executed for every rotated file with the strategy "command",
+# e.g. to ship old backups to tape or cold storage.
+# The command must remove the file or move it out of "path" itself,
+# Vault only records it in the journal. A file still matching
+# "rotate_glob" afterwards is rotated again by the next run.
+# It can contain the same macro keywords as "commands", and also:
+# "{BUCKET}" = Bucket the file was rotated out of (e.g. "2024-05"),
+#              or the rule that pruned it (e.g. "max_age").
+# "{FILE}" = Path of the rotated file.
+# Example: "/usr/local/bin/to-tape {FILE} {BUCKET}"
+# Default: ""
+rotate_command = ""
+
+# Rotate glob.
+# Restricts rotation to the files inside "path" matching this glob.
+# "*" matches any text and "?" matches any single character.
+# Example: "*.tar.gz"
+# Default: "*"
+rotate_glob = "*"
+
+# Rotate move target.
+# Directory the "move" strategy moves files into.
+# It can contain the same macro keywords as "commands", except those
+# changing from run to run ("{DATE}", "{NOW}", "{SEQ}", "{STAMP}" and
+# "{TIME}"), so moved files can be found again by "trash" and expiry.
+# Relative paths are relative to "path".
+# If the directory is on another file system,
+# files are copied, synced, verified and deleted instead of renamed.
+# If it is inside "path", it is never rotated itself.
+# It must neither be "path" itself nor contain it.
+# Example: "/mnt/archive/{HOST}/{TASK}"
+# Default: "{PATH}/moved"
+rotate_move_target = "{PATH}/moved"
+
+# Rotate source.
+# Defines where the time and date of a backup is taken from for rotation.
+# The following sources are possible:
+# "ctime" = Change time of the file (creation time, where not available).
+# "mtime" = Modification time of the file.
+# "name" = Stamp inside the file name (See "file_pattern").
+# Default: "name"
+rotate_source = "name"
+
 # Rotate strategy being used.
 # Defines what exactly happens to a file when rotation strikes.
 # The following strategies are possible:
+# "archive" = Files rotated out of a bucket are bundled into one compressed
+#             tarball inside "rotate_move_target", named after the bucket
+#             (e.g. "2024-05.tar.zst"), next to an index of its contents
+#             (e.g. "2024-05.tar.zst.index.toml").
+#             Files of rules without buckets ("keep", "max_age" and
+#             "max_total_size") are named after the rule and the stamp of
+#             the rotation (e.g. "keep-2024-05-20_04-55-11.007757100.tar.zst").
+#             Existing archives get a number appended instead of being replaced.
+# "command" = File will be handed over to "rotate_command".
 # "delete" = File will be deleted and can not be recovered.
-# "move" = File will be moved into "{PATH}/moved".
-#          If "{PATH}/moved" does not exist, it will be created.
+# "move" = File will be moved into "rotate_move_target".
+#          If "rotate_move_target" does not exist, it will be created.
+# Every rotated file is recorded with its size and SHA-256 checksum
+# in the append-only "journal.toml" inside "path".
+# Files of a "move" run can be moved back with
+# "vault -c <FILE> undo-rotation <TASK> <RUN>".
 # Default: "move"
 rotate_strategy = "move"
 
@@ -195,4 +568,36 @@ rotate_strategy = "move"
 # For example, a task could take longer than the interval defines.
 # Default: true
 singleton = true
+
+# Stamp format.
+# Format of "{STAMP}" in strftime syntax,
+# also used to parse the stamps of existing backups (See "file_pattern").
+# If the format has no time or no day, the stamp starts at the
+# beginning of the day or month.
+# An offset after the stamp (e.g. "20230520+0200") is optional.
+# Without an offset, stamps are read as times of "timezone".
+# Example: "%Y%m%d" = 20230520
+# Default: "%Y-%m-%d_%H-%M-%S%.9f"
+stamp_format = "%Y-%m-%d_%H-%M-%S%.9f"
+
+# Variables of this task.
+# Like the variables of the configuration (See "vars" above),
+# overriding those with the same name.
+# Default: (empty)
+vars = {source = "/srv/{var:host}"}
+
+# Maximum size deviation of a verified artifact.
+# After the commands, each verified artifact (See "artifacts") must exist
+# as a backup of this run, otherwise the run fails and rotation is skipped.
+# Its size is also compared to the previous backup of the same artifact,
+# the run fails if it differs by more than this fraction (0.5 = 50%).
+# 0 = Disabled.
+# Default: 0
+verify_max_deviation = 0.5
+
+# Minimum size of a verified artifact.
+# A verified artifact (See "artifacts") smaller than this fails the run.
+# Units: B, KB, KiB, MB, MiB, GB, GiB, TB and TiB.
+# Default: "1"
+verify_min_size = "1"
 "#;
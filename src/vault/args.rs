@@ -1,6 +1,7 @@
 // Use
 use clap::CommandFactory;
 use clap::Parser;
+use clap::Subcommand;
 use std::path::PathBuf;
 
 /// Args struct
@@ -11,6 +12,16 @@ use std::path::PathBuf;
 #[command(version)]
 pub struct Args
 {
+	/// Allow mass rotate
+	///
+	/// Rotate even if a run exceeds "max_rotate_count" or "max_rotate_fraction".
+	#[arg(long, global = true, default_value_t = false)]
+	pub allow_mass_rotate: bool,
+
+	/// Command
+	#[command(subcommand)]
+	pub command: Option<ArgsCommand>,
+
 	/// Config file
 	///
 	/// Empty = Show help.
@@ -37,6 +48,119 @@ pub struct Args
 	/// * = Do all tasks.
 	#[arg(short, long, value_name = "TASK")]
 	pub task: Option<String>,
+
+	/// Wait for locked tasks
+	///
+	/// Blocks until a locked singleton task is released.
+	/// Empty = Wait without timeout.
+	/// Otherwise = Wait up to the given number of seconds, then fail.
+	#[arg(short, long, value_name = "SECONDS", num_args = 0..=1, default_missing_value = "0")]
+	pub wait: Option<u64>,
+}
+
+/// ArgsCommand enum
+#[derive(Debug, Subcommand)]
+pub enum ArgsCommand
+{
+	/// Pin a backup, which exempts it from rotation
+	Pin
+	{
+		/// Task of the backup
+		#[arg(value_name = "TASK")]
+		task: String,
+
+		/// File name of the backup inside the path of the task
+		#[arg(value_name = "FILE")]
+		file: String,
+
+		/// Pin by creating a "<FILE>.keep" marker instead of recording it in the state
+		#[arg(long, default_value_t = false)]
+		marker: bool,
+	},
+
+	/// Rotate a task without executing its commands
+	///
+	/// Ignores the interval of the task.
+	#[command(visible_alias = "prune")]
+	Rotate
+	{
+		/// Task to be rotated
+		///
+		/// * = Rotate all enabled tasks.
+		#[arg(value_name = "TASK")]
+		task: String,
+
+		/// Plan only
+		///
+		/// Shows which files are kept and which would be moved or deleted,
+		/// per bucket and with the rule responsible, without changing anything.
+		#[arg(long, visible_alias = "dry-run", default_value_t = false)]
+		plan: bool,
+
+		/// Output the plan as JSON
+		#[arg(long, requires = "plan", default_value_t = false)]
+		json: bool,
+	},
+
+	/// Manage the moved directory of a task
+	Trash
+	{
+		/// Trash command
+		#[command(subcommand)]
+		command: ArgsTrash,
+	},
+
+	/// Undo a rotation run by moving its files back into the path of the task
+	///
+	/// Only files rotated with the strategy "move" can be restored.
+	/// The runs are listed in the "journal.toml" of the task.
+	UndoRotation
+	{
+		/// Task of the rotation
+		#[arg(value_name = "TASK")]
+		task: String,
+
+		/// Run of the rotation, as reported by it
+		#[arg(value_name = "RUN")]
+		run: String,
+	},
+
+	/// Unpin a backup, removing it from the state and removing its marker
+	Unpin
+	{
+		/// Task of the backup
+		#[arg(value_name = "TASK")]
+		task: String,
+
+		/// File name of the backup inside the path of the task
+		#[arg(value_name = "FILE")]
+		file: String,
+	},
+}
+
+/// ArgsTrash enum
+#[derive(Debug, Subcommand)]
+pub enum ArgsTrash
+{
+	/// Delete all files in the moved directory
+	Empty
+	{
+		/// Task of the moved directory
+		#[arg(value_name = "TASK")]
+		task: String,
+	},
+
+	/// Move a file from the moved directory back into the path of the task
+	Restore
+	{
+		/// Task of the moved directory
+		#[arg(value_name = "TASK")]
+		task: String,
+
+		/// File name inside the moved directory
+		#[arg(value_name = "FILE")]
+		file: String,
+	},
 }
 
 /// Args impl
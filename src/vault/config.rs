@@ -1,16 +1,24 @@
 // Use
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::fs;
 use std::path::PathBuf;
 use std::vec::Vec;
 use serde::Deserialize;
+use crate::vault::macros::{BUILTIN, MACRO_PATH, MACRO_STAMP, Macros, RUN};
+use crate::vault::size::Size;
+use crate::vault::rotate::{DIRECTORY_MOVE, Rotate};
+use crate::vault::step::{STEP_ARCHIVE, STEP_ARCHIVE_COMPRESSIONS, STEP_COMMAND, STEP_SNAPSHOT, STEP_SYMLINKS, STEP_ZIP, STEP_ZIP_COMPRESSIONS};
+use crate::vault::time::{FORMAT, Time, Zone};
 
 // Config struct
 #[derive(Clone, Debug, Deserialize)]
 #[serde(default)]
 pub struct Config
 {
+	/// Allow mass rotate
+	pub allow_mass_rotate: bool,
+
 	/// Debug
 	pub debug: bool,
 
@@ -19,6 +27,15 @@ pub struct Config
 
 	/// Tasks
 	pub tasks: HashMap<String, ConfigTask>,
+
+	/// Timezone
+	pub timezone: String,
+
+	/// Vars
+	pub vars: BTreeMap<String, String>,
+
+	/// Wait
+	pub wait: Option<u64>,
 }
 
 /// Default impl for Config
@@ -29,9 +46,13 @@ impl Default for Config
 	{
 		Config
 		{
+			allow_mass_rotate: false,
 			debug: false,
 			name: String::new(),
 			tasks: HashMap::new(),
+			timezone: String::from("UTC"),
+			vars: BTreeMap::new(),
+			wait: None,
 		}
 	}
 }
@@ -44,7 +65,7 @@ impl Config
 	{
 		if !self.tasks.contains_key(a_task)
 		{
-			println!("Error: {}.{} does not exist!", self.name, a_task);
+			eprintln!("Error: {}.{} does not exist!", self.name, a_task);
 			return None;
 		}
 		match self.tasks.get(a_task)
@@ -52,12 +73,26 @@ impl Config
 			Some(m_task) => return Some(m_task.clone()),
 			None =>
 			{
-				println!("Error: {}.{} does not exist!", self.name, a_task);
+				eprintln!("Error: {}.{} does not exist!", self.name, a_task);
 				return None;
 			},
 		}
 	}
 
+	/// Get zone
+	///
+	/// The timezone is validated on load, so this falls back to UTC.
+	pub fn get_zone(&self) -> Zone
+	{
+		return Time::zone(&self.timezone).unwrap_or(Zone::Tz(chrono_tz::UTC));
+	}
+
+	/// Is UTC
+	pub fn is_utc(&self) -> bool
+	{
+		return matches!(self.get_zone(), Zone::Tz(chrono_tz::UTC) | Zone::Tz(chrono_tz::Etc::UTC));
+	}
+
 	/// Load
 	pub fn load(a_path: &PathBuf) -> Option<Config>
 	{
@@ -70,12 +105,12 @@ impl Config
 			l_path = a_path.to_path_buf();
 			if !l_path.exists()
 			{
-				println!("Error: Configuration file '{}' does not exist!", a_path.display());
+				eprintln!("Error: Configuration file '{}' does not exist!", a_path.display());
 				return None;
 			}
 			if !l_path.is_file()
 			{
-				println!("Error: Configuration file '{}' is not a file!", a_path.display());
+				eprintln!("Error: Configuration file '{}' is not a file!", a_path.display());
 				return None;
 			}
 		}
@@ -92,14 +127,14 @@ impl Config
 						Ok(m_path) => m_path,
 						Err(m_error) =>
 						{
-							println!("Error: Failed to canonicalize configuration file '{}'!\n{}", a_path.display(), m_error.to_string());
+							eprintln!("Error: Failed to canonicalize configuration file '{}'!\n{}", a_path.display(), m_error.to_string());
 							return None;
 						}
 					};
 				},
 				Err(m_error) =>
 				{
-					println!("Error: Failed to get current directory for configuration file '{}'!\n{}", a_path.display(), m_error.to_string());
+					eprintln!("Error: Failed to get current directory for configuration file '{}'!\n{}", a_path.display(), m_error.to_string());
 					return None;
 				},
 			}
@@ -111,7 +146,7 @@ impl Config
 			Ok(m_data) => m_data,
 			Err(m_error) =>
 			{
-				println!("Error: Failed to read configuration file '{}'!\n{}", a_path.display(), m_error.to_string());
+				eprintln!("Error: Failed to read configuration file '{}'!\n{}", a_path.display(), m_error.to_string());
 				return None;
 			}
 		};
@@ -122,16 +157,60 @@ impl Config
 			Ok(m_config) => m_config,
 			Err(m_error) =>
 			{
-				println!("Error: Failed to parse configuration file '{}'!\n{}", a_path.display(), m_error.to_string());
+				eprintln!("Error: Failed to parse configuration file '{}'!\n{}", a_path.display(), m_error.to_string());
 				return None;
 			}
 		};
 
+		// Unknown timezone
+		if Time::zone(&l_config.timezone).is_none()
+		{
+			eprintln!("Error: Configuration file '{}' has an unknown timezone '{}'!", a_path.display(), l_config.timezone);
+			return None;
+		}
+
 		// Iterate over task and assign their config and task strings
+		let l_zone = l_config.get_zone();
 		for (i_k, i_v) in l_config.tasks.iter_mut()
 		{
 			i_v.config = l_config.name.clone();
 			i_v.task = i_k.clone();
+
+			// Merge variables, the task ones take precedence
+			let mut l_vars = l_config.vars.clone();
+			l_vars.append(&mut i_v.vars);
+			i_v.vars = l_vars;
+
+			// Cycle in variables
+			if let Some(m_cycle) = Macros::cycle(&i_v.vars)
+			{
+				eprintln!("Error: Configuration file '{}' has a cycle in the variables of task '{}' ({})!", a_path.display(), i_k, m_cycle.join(" -> "));
+				return None;
+			}
+
+			// Invalid macros in path
+			let l_path = i_v.path.to_string_lossy().to_string();
+			if let Err(m_error) = Macros::check(&l_path, &[], &i_v.vars)
+			{
+				eprintln!("Error: Configuration file '{}' has an invalid path in task '{}' ({})!", a_path.display(), i_k, m_error);
+				return None;
+			}
+
+			// Path changing from run to run, or referring to itself
+			if let Some(m_name) = Macros::find(&l_path, &[RUN.as_slice(), &["PATH"]].concat(), &i_v.vars)
+			{
+				eprintln!("Error: Configuration file '{}' has a path in task '{}' using '{{{}}}', which is not available there!", a_path.display(), i_k, m_name);
+				return None;
+			}
+
+			// Evaluate macros in path
+			let mut l_macros = Macros::new(&Time::now(), "", &l_zone);
+			l_macros
+				.set("CONFIG", &l_config.name)
+				.set("TASK", i_k)
+				.set_vars(&i_v.vars)
+			;
+			i_v.path = PathBuf::from(l_macros.eval(&l_path));
 		}
 
 		// Done
@@ -145,8 +224,11 @@ impl Config
 #[serde(default)]
 pub struct ConfigTask
 {
+	/// Artifacts
+	pub artifacts: Vec<ConfigTaskArtifactEntry>,
+
 	/// Commands
-	pub commands: Vec<String>,
+	pub commands: Vec<ConfigTaskCommand>,
 
 	/// Config
 	pub config: String,
@@ -154,9 +236,30 @@ pub struct ConfigTask
 	/// Enabled
 	pub enabled: bool,
 
+	/// File pattern
+	pub file_pattern: String,
+
 	/// Interval
 	pub interval: i64,
 
+	/// Max rotate count
+	pub max_rotate_count: u64,
+
+	/// Max rotate fraction
+	pub max_rotate_fraction: f64,
+
+	/// Moved max age
+	pub moved_max_age: String,
+
+	/// Moved max size
+	pub moved_max_size: String,
+
+	/// On locked
+	pub on_locked: String,
+
+	/// On locked timeout
+	pub on_locked_timeout: u64,
+
 	/// Order
 	pub order: u64,
 
@@ -166,14 +269,38 @@ pub struct ConfigTask
 	/// Rotate
 	pub rotate: ConfigTaskRotate,
 
+	/// Rotate command
+	pub rotate_command: String,
+
+	/// Rotate glob
+	pub rotate_glob: String,
+
+	/// Rotate move target
+	pub rotate_move_target: String,
+
+	/// Rotate source
+	pub rotate_source: String,
+
 	/// Rotate strategy
 	pub rotate_strategy: String,
 
 	/// Singleton
 	pub singleton: bool,
 
+	/// Stamp format
+	pub stamp_format: String,
+
 	/// Task
 	pub task: String,
+
+	/// Vars
+	pub vars: BTreeMap<String, String>,
+
+	/// Verify max deviation
+	pub verify_max_deviation: f64,
+
+	/// Verify min size
+	pub verify_min_size: String,
 }
 
 /// Default impl for ConfigTask
@@ -184,16 +311,32 @@ impl Default for ConfigTask
 	{
 		ConfigTask
 		{
+			artifacts: Vec::new(),
 			commands: Vec::new(),
 			config: String::new(),
 			enabled: false,
+			file_pattern: String::from(MACRO_STAMP) + "*",
 			interval: 0,
+			max_rotate_count: 0,
+			max_rotate_fraction: 0.0,
+			moved_max_age: String::new(),
+			moved_max_size: String::new(),
+			on_locked: String::from("skip"),
+			on_locked_timeout: 0,
 			order: 0,
 			path: PathBuf::new(),
 			rotate: ConfigTaskRotate::default(),
+			rotate_command: String::new(),
+			rotate_glob: String::from("*"),
+			rotate_move_target: String::from(MACRO_PATH) + "/" + DIRECTORY_MOVE,
+			rotate_source: String::from("name"),
 			rotate_strategy: String::from("move"),
 			singleton: true,
+			stamp_format: String::from(FORMAT),
 			task: String::new(),
+			vars: BTreeMap::new(),
+			verify_max_deviation: 0.0,
+			verify_min_size: String::from("1"),
 		}
 	}
 }
@@ -201,8 +344,47 @@ impl Default for ConfigTask
 /// Task impl
 impl ConfigTask
 {
-	/// Valid
-	pub fn is_valid(&self) -> bool
+	/// Get artifacts
+	///
+	/// Without configured artifacts,
+	/// the task itself describes one artifact group.
+	pub fn get_artifacts(&self) -> Vec<ConfigTaskArtifact>
+	{
+		if !self.artifacts.is_empty()
+		{
+			return self.artifacts
+				.iter()
+				.map(|a_entry| match a_entry
+				{
+					ConfigTaskArtifactEntry::Pattern(m_pattern) => ConfigTaskArtifact
+					{
+						file_pattern: m_pattern.clone(),
+						name: String::new(),
+						rotate: self.rotate.clone(),
+						rotate_glob: self.rotate_glob.clone(),
+						rotate_source: self.rotate_source.clone(),
+						verify: true,
+					},
+					ConfigTaskArtifactEntry::Table(m_table) => m_table.clone(),
+				})
+				.collect()
+			;
+		}
+		return vec![ConfigTaskArtifact
+		{
+			file_pattern: self.file_pattern.clone(),
+			name: String::new(),
+			rotate: self.rotate.clone(),
+			rotate_glob: self.rotate_glob.clone(),
+			rotate_source: self.rotate_source.clone(),
+			verify: false,
+		}];
+	}
+
+	/// Is enabled
+	///
+	/// Checks whether the task runs at all, a disabled task is skipped rather than failed.
+	pub fn is_enabled(&self) -> bool
 	{
 		// Task not enabled
 		if !self.enabled
@@ -218,6 +400,151 @@ impl ConfigTask
 			return false;
 		}
 
+		// Done
+		return true;
+	}
+
+	/// Valid
+	pub fn is_valid(&self) -> bool
+	{
+		// Task not enabled
+		if !self.is_enabled()
+		{
+			return false;
+		}
+
+		// Unknown lock policy
+		match self.on_locked.as_str()
+		{
+			"fail" | "skip" | "wait" => {},
+			_ =>
+			{
+				eprintln!("Error: {}.{} unknown lock policy '{}'!", self.config, self.task, self.on_locked);
+				return false;
+			},
+		}
+
+		// Invalid max rotate fraction
+		if !(0.0..=1.0).contains(&self.max_rotate_fraction)
+		{
+			eprintln!("Error: {}.{} invalid max rotate fraction '{}'!", self.config, self.task, self.max_rotate_fraction);
+			return false;
+		}
+
+		// Invalid moved max age
+		if !self.moved_max_age.is_empty() && Time::duration(&self.moved_max_age).is_none()
+		{
+			eprintln!("Error: {}.{} invalid moved max age '{}'!", self.config, self.task, self.moved_max_age);
+			return false;
+		}
+
+		// Invalid moved max size
+		if !self.moved_max_size.is_empty() && Size::from_string(&self.moved_max_size).is_none()
+		{
+			eprintln!("Error: {}.{} invalid moved max size '{}'!", self.config, self.task, self.moved_max_size);
+			return false;
+		}
+
+		// Get macros of the newest backups, only known to commands
+		let mut l_last = vec![String::from("LAST"), String::from("PREV")];
+		for i_artifact in self.get_artifacts().iter().filter(|a_artifact| !a_artifact.name.is_empty())
+		{
+			l_last.push(format!("LAST:{}", i_artifact.name));
+			l_last.push(format!("PREV:{}", i_artifact.name));
+		}
+		let l_last = l_last.iter().map(|a_name| a_name.as_str()).collect::<Vec<&str>>();
+
+		// Invalid commands, which can use the captures of earlier ones
+		let mut l_vars = self.vars.clone();
+		for i_cmd in self.commands.iter()
+		{
+			if !i_cmd.is_valid(self, &l_last, &l_vars)
+			{
+				return false;
+			}
+			if !i_cmd.capture().is_empty()
+			{
+				l_vars.insert(i_cmd.capture().to_string(), String::new());
+			}
+		}
+
+		// Invalid macros
+		let l_fields =
+		[
+			("rotate command", &self.rotate_command, vec!["BUCKET", "FILE"]),
+			("rotate move target", &self.rotate_move_target, Vec::new()),
+		]
+			.into_iter()
+			.chain(self.vars.values().map(|a_var| ("variable", a_var, [vec!["BUCKET", "FILE"], l_last.clone()].concat())))
+		;
+		for (i_field, i_str, i_extra) in l_fields
+		{
+			if let Err(m_error) = Macros::check(i_str, &i_extra, &self.vars)
+			{
+				eprintln!("Error: {}.{} {} '{}' has an {}!", self.config, self.task, i_field, i_str, m_error);
+				return false;
+			}
+		}
+
+		// Move target changing from run to run, where trash and expiry would not find earlier moves
+		if let Some(m_name) = Macros::find(&self.rotate_move_target, &RUN, &self.vars)
+		{
+			eprintln!("Error: {}.{} rotate move target '{}' must not use '{{{}}}', which changes from run to run!", self.config, self.task, self.rotate_move_target, m_name);
+			return false;
+		}
+
+		// Move target being or containing the path, where trash and expiry would handle the backups themselves
+		let mut l_macros = Macros::new(&Time::now(), "", &Zone::Local);
+		l_macros
+			.set("CONFIG", &self.config)
+			.set("PATH", &self.path.to_string_lossy())
+			.set("TASK", &self.task)
+			.set_vars(&self.vars)
+		;
+		let l_target = Rotate::normalize(&self.path.join(l_macros.eval(&self.rotate_move_target)));
+		if Rotate::normalize(&self.path).starts_with(&l_target)
+		{
+			eprintln!("Error: {}.{} rotate move target '{}' must not be or contain the path!", self.config, self.task, self.rotate_move_target);
+			return false;
+		}
+
+		// Rotate command missing
+		if self.rotate_strategy == "command" && self.rotate_command.is_empty()
+		{
+			eprintln!("Error: {}.{} rotate strategy 'command' requires a rotate command!", self.config, self.task);
+			return false;
+		}
+
+		// Invalid verify max deviation
+		if self.verify_max_deviation < 0.0
+		{
+			eprintln!("Error: {}.{} invalid verify max deviation '{}'!", self.config, self.task, self.verify_max_deviation);
+			return false;
+		}
+
+		// Invalid verify min size
+		if Size::from_string(&self.verify_min_size).is_none()
+		{
+			eprintln!("Error: {}.{} invalid verify min size '{}'!", self.config, self.task, self.verify_min_size);
+			return false;
+		}
+
+		// Invalid stamp format
+		if Time::regex(&self.stamp_format).is_none()
+		{
+			eprintln!("Error: {}.{} invalid stamp format '{}'!", self.config, self.task, self.stamp_format);
+			return false;
+		}
+
+		// Invalid artifact
+		for i_artifact in self.get_artifacts()
+		{
+			if !i_artifact.is_valid(self)
+			{
+				return false;
+			}
+		}
+
 		// No path
 		match self.path.to_str()
 		{
@@ -241,6 +568,326 @@ impl ConfigTask
 	}
 }
 
+/// ConfigTaskArtifact struct
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ConfigTaskArtifact
+{
+	/// File pattern
+	pub file_pattern: String,
+
+	/// Name
+	pub name: String,
+
+	/// Rotate
+	pub rotate: ConfigTaskRotate,
+
+	/// Rotate glob
+	pub rotate_glob: String,
+
+	/// Rotate source
+	pub rotate_source: String,
+
+	/// Verify
+	pub verify: bool,
+}
+
+/// Default impl for ConfigTaskArtifact
+impl Default for ConfigTaskArtifact
+{
+	/// Default
+	fn default() -> ConfigTaskArtifact
+	{
+		ConfigTaskArtifact
+		{
+			file_pattern: String::from(MACRO_STAMP) + "*",
+			name: String::new(),
+			rotate: ConfigTaskRotate::default(),
+			rotate_glob: String::from("*"),
+			rotate_source: String::from("name"),
+			verify: false,
+		}
+	}
+}
+
+/// ConfigTaskArtifact impl
+impl ConfigTaskArtifact
+{
+	/// Is valid
+	pub fn is_valid(&self, a_task: &ConfigTask) -> bool
+	{
+		// Invalid file pattern
+		if self.rotate_source == "name" && self.file_pattern.matches(MACRO_STAMP).count() != 1
+		{
+			eprintln!("Error: {}.{} file pattern '{}' must contain {} exactly once!", a_task.config, a_task.task, self.file_pattern, MACRO_STAMP);
+			return false;
+		}
+
+		// Unknown rotate source
+		match self.rotate_source.as_str()
+		{
+			"ctime" | "mtime" | "name" => {},
+			_ =>
+			{
+				eprintln!("Error: {}.{} unknown rotate source '{}'!", a_task.config, a_task.task, self.rotate_source);
+				return false;
+			},
+		}
+
+		// Invalid max age
+		if !self.rotate.max_age.is_empty() && Time::duration(&self.rotate.max_age).is_none()
+		{
+			eprintln!("Error: {}.{} invalid max age '{}'!", a_task.config, a_task.task, self.rotate.max_age);
+			return false;
+		}
+
+		// Invalid max total size
+		if !self.rotate.max_total_size.is_empty() && Size::from_string(&self.rotate.max_total_size).is_none()
+		{
+			eprintln!("Error: {}.{} invalid max total size '{}'!", a_task.config, a_task.task, self.rotate.max_total_size);
+			return false;
+		}
+
+		// Done
+		return true;
+	}
+}
+
+/// ConfigTaskArtifactEntry enum
+///
+/// An artifact is either a file pattern, expected after every run,
+/// or a table with its own settings.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ConfigTaskArtifactEntry
+{
+	/// File pattern
+	Pattern(String),
+
+	/// Artifact with settings
+	Table(ConfigTaskArtifact),
+}
+
+/// ConfigTaskCommand enum
+///
+/// A command is either a plain command line or a table with options.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ConfigTaskCommand
+{
+	/// Command line
+	Line(String),
+
+	/// Command with options
+	Table(Box<ConfigTaskCommandTable>),
+}
+
+/// ConfigTaskCommand impl
+impl ConfigTaskCommand
+{
+	/// Capture
+	pub fn capture(&self) -> &str
+	{
+		match self
+		{
+			ConfigTaskCommand::Table(m_table) if m_table.kind == STEP_COMMAND => return &m_table.capture,
+			_ => return "",
+		}
+	}
+
+	/// Command
+	pub fn command(&self) -> &str
+	{
+		match self
+		{
+			ConfigTaskCommand::Line(m_line) => return m_line,
+			ConfigTaskCommand::Table(m_table) => return &m_table.command,
+		}
+	}
+
+	/// Is valid
+	///
+	/// Checks the macros of a command, or the settings of a built-in step.
+	pub fn is_valid(&self, a_task: &ConfigTask, a_last: &[&str], a_vars: &BTreeMap<String, String>) -> bool
+	{
+		// Get step
+		let l_step = match self.step()
+		{
+			Some(m_step) => m_step,
+			None =>
+			{
+				// Invalid macros of command
+				if let Err(m_error) = Macros::check(self.command(), a_last, a_vars)
+				{
+					eprintln!("Error: {}.{} command '{}' has an {}!", a_task.config, a_task.task, self.command(), m_error);
+					return false;
+				}
+
+				// No capture
+				let l_capture = self.capture();
+				if l_capture.is_empty()
+				{
+					return true;
+				}
+
+				// Invalid capture name
+				if !Macros::is_name(l_capture)
+				{
+					eprintln!("Error: {}.{} command '{}' has an invalid capture name '{}', use only letters, digits, '_' and '-'!", a_task.config, a_task.task, self.command(), l_capture);
+					return false;
+				}
+
+				// Capture name taken by a variable or built-in macro
+				if a_task.vars.contains_key(l_capture) || BUILTIN.contains(&l_capture)
+				{
+					eprintln!("Error: {}.{} command '{}' has a capture name '{}', which is already taken by a variable or macro!", a_task.config, a_task.task, self.command(), l_capture);
+					return false;
+				}
+				return true;
+			}
+		};
+
+		// Get compressions of type
+		let l_compressions = match l_step.kind.as_str()
+		{
+			STEP_ARCHIVE => STEP_ARCHIVE_COMPRESSIONS.as_slice(),
+			STEP_SNAPSHOT => [].as_slice(),
+			STEP_ZIP => STEP_ZIP_COMPRESSIONS.as_slice(),
+			_ =>
+			{
+				eprintln!("Error: {}.{} unknown step type '{}'!", a_task.config, a_task.task, l_step.kind);
+				return false;
+			}
+		};
+
+		// Unknown compression, empty for the default of the type
+		if !l_step.compression.is_empty() && !l_compressions.contains(&l_step.compression.as_str())
+		{
+			eprintln!("Error: {}.{} {} step has an unknown compression '{}'!", a_task.config, a_task.task, l_step.kind, l_step.compression);
+			return false;
+		}
+
+		// Unknown symlink policy
+		if !STEP_SYMLINKS.contains(&l_step.symlinks.as_str())
+		{
+			eprintln!("Error: {}.{} {} step has an unknown symlink policy '{}'!", a_task.config, a_task.task, l_step.kind, l_step.symlinks);
+			return false;
+		}
+
+		// Password without encryption
+		if !l_step.password.is_empty() && l_step.kind != STEP_ZIP
+		{
+			eprintln!("Error: {}.{} {} step can not be encrypted with a password!", a_task.config, a_task.task, l_step.kind);
+			return false;
+		}
+
+		// Snapshot without stamp, which would be overwritten
+		if l_step.kind == STEP_SNAPSHOT && !l_step.file.is_empty() && !l_step.file.contains(MACRO_STAMP)
+		{
+			eprintln!("Error: {}.{} snapshot step file '{}' must contain '{}'!", a_task.config, a_task.task, l_step.file, MACRO_STAMP);
+			return false;
+		}
+
+		// No sources
+		if l_step.sources.is_empty()
+		{
+			eprintln!("Error: {}.{} {} step has no sources!", a_task.config, a_task.task, l_step.kind);
+			return false;
+		}
+
+		// Invalid macros of password, without showing it
+		if let Err(m_error) = Macros::check(&l_step.password, a_last, a_vars)
+		{
+			eprintln!("Error: {}.{} {} step password has an {}!", a_task.config, a_task.task, l_step.kind, m_error);
+			return false;
+		}
+
+		// Invalid macros of file and sources
+		for i_str in [&l_step.file].into_iter().chain(l_step.sources.iter())
+		{
+			if let Err(m_error) = Macros::check(i_str, a_last, a_vars)
+			{
+				eprintln!("Error: {}.{} {} step '{}' has an {}!", a_task.config, a_task.task, l_step.kind, i_str, m_error);
+				return false;
+			}
+		}
+
+		// Done
+		return true;
+	}
+
+	/// Step
+	///
+	/// Gets the settings of a built-in step, none for a command.
+	pub fn step(&self) -> Option<&ConfigTaskCommandTable>
+	{
+		match self
+		{
+			ConfigTaskCommand::Table(m_table) if m_table.kind != STEP_COMMAND => return Some(m_table.as_ref()),
+			_ => return None,
+		}
+	}
+}
+
+/// ConfigTaskCommandTable struct
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ConfigTaskCommandTable
+{
+	/// Capture
+	pub capture: String,
+
+	/// Command
+	pub command: String,
+
+	/// Compression
+	pub compression: String,
+
+	/// Exclude
+	pub exclude: Vec<String>,
+
+	/// File
+	pub file: String,
+
+	/// Include
+	pub include: Vec<String>,
+
+	/// Password
+	pub password: String,
+
+	/// Sources
+	pub sources: Vec<String>,
+
+	/// Symlinks
+	pub symlinks: String,
+
+	/// Type
+	#[serde(rename = "type")]
+	pub kind: String,
+}
+
+/// Default impl for ConfigTaskCommandTable
+impl Default for ConfigTaskCommandTable
+{
+	/// Default
+	fn default() -> ConfigTaskCommandTable
+	{
+		ConfigTaskCommandTable
+		{
+			capture: String::new(),
+			command: String::new(),
+			compression: String::new(),
+			exclude: Vec::new(),
+			file: String::new(),
+			include: Vec::new(),
+			password: String::new(),
+			sources: Vec::new(),
+			symlinks: String::from("keep"),
+			kind: String::from(STEP_COMMAND),
+		}
+	}
+}
+
 /// ConfigTaskRotate struct
 #[derive(Clone, Debug, Deserialize)]
 #[serde(default)]
@@ -252,9 +899,39 @@ pub struct ConfigTaskRotate
 	/// Hourly
 	pub hourly: bool,
 
+	/// Keep daily
+	pub keep_daily: u64,
+
+	/// Keep hourly
+	pub keep_hourly: u64,
+
+	/// Keep last
+	pub keep_last: u64,
+
+	/// Keep monthly
+	pub keep_monthly: u64,
+
+	/// Keep weekly
+	pub keep_weekly: u64,
+
+	/// Keep yearly
+	pub keep_yearly: u64,
+
+	/// Max age
+	pub max_age: String,
+
+	/// Max total size
+	pub max_total_size: String,
+
+	/// Min keep
+	pub min_keep: u64,
+
 	/// Monthly
 	pub monthly: bool,
 
+	/// Weekly
+	pub weekly: bool,
+
 	/// Yearly
 	pub yearly: bool,
 }
@@ -269,7 +946,17 @@ impl Default for ConfigTaskRotate
 		{
 			daily: false,
 			hourly: false,
+			keep_daily: 0,
+			keep_hourly: 0,
+			keep_last: 0,
+			keep_monthly: 0,
+			keep_weekly: 0,
+			keep_yearly: 0,
+			max_age: String::new(),
+			max_total_size: String::new(),
+			min_keep: 1,
 			monthly: false,
+			weekly: false,
 			yearly: false,
 		}
 	}
@@ -278,9 +965,21 @@ impl Default for ConfigTaskRotate
 /// ConfigTaskRotate impl
 impl ConfigTaskRotate
 {
+	/// Is keep
+	pub fn is_keep(&self) -> bool
+	{
+		self.keep_daily > 0 || self.keep_hourly > 0 || self.keep_last > 0 || self.keep_monthly > 0 || self.keep_weekly > 0 || self.keep_yearly > 0
+	}
+
+	/// Is prune
+	pub fn is_prune(&self) -> bool
+	{
+		!self.max_age.is_empty() || !self.max_total_size.is_empty()
+	}
+
 	/// Is valid
 	pub fn is_valid(&self) -> bool
 	{
-		self.daily || self.hourly || self.monthly || self.yearly
+		self.daily || self.hourly || self.monthly || self.weekly || self.yearly || self.is_keep() || self.is_prune()
 	}
 }
@@ -0,0 +1,244 @@
+// Use
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// File journal
+pub const FILE_JOURNAL: &str = "journal.toml";
+
+/// Journal struct
+///
+/// Append-only record of everything rotation did to the backups of a task.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Journal
+{
+	/// Rotation
+	pub rotation: Vec<JournalEntry>,
+}
+
+/// Default impl for Journal
+impl Default for Journal
+{
+	/// Default
+	fn default() -> Journal
+	{
+		Journal
+		{
+			rotation: Vec::new(),
+		}
+	}
+}
+
+/// JournalEntry struct
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct JournalEntry
+{
+	/// Bucket
+	pub bucket: String,
+
+	/// Checksum
+	pub checksum: String,
+
+	/// File
+	pub file: String,
+
+	/// Rule
+	pub rule: String,
+
+	/// Run
+	pub run: String,
+
+	/// Size
+	pub size: u64,
+
+	/// Strategy
+	pub strategy: String,
+
+	/// Target
+	pub target: String,
+
+	/// Task
+	pub task: String,
+
+	/// Time
+	pub time: String,
+}
+
+/// Default impl for JournalEntry
+impl Default for JournalEntry
+{
+	/// Default
+	fn default() -> JournalEntry
+	{
+		JournalEntry
+		{
+			bucket: String::new(),
+			checksum: String::new(),
+			file: String::new(),
+			rule: String::new(),
+			run: String::new(),
+			size: 0,
+			strategy: String::new(),
+			target: String::new(),
+			task: String::new(),
+			time: String::new(),
+		}
+	}
+}
+
+/// Journal impl
+impl Journal
+{
+	/// Append
+	///
+	/// Appends entries to the journal, without touching earlier ones.
+	pub fn append(a_path: &Path, a_entries: &[JournalEntry]) -> bool
+	{
+		// Nothing to append
+		if a_entries.is_empty()
+		{
+			return true;
+		}
+
+		// Serialize entries
+		let l_path = Journal::path(a_path);
+		let l_data = match toml::to_string(&Journal { rotation: a_entries.to_vec() })
+		{
+			Ok(m_data) => m_data,
+			Err(m_error) =>
+			{
+				eprintln!("Error: Failed to construct journal file '{}'!\n{}", l_path.display(), m_error.to_string());
+				return false;
+			}
+		};
+
+		// Open file for appending
+		let mut l_file = match OpenOptions::new().create(true).append(true).open(&l_path)
+		{
+			Ok(m_file) => m_file,
+			Err(m_error) =>
+			{
+				eprintln!("Error: Failed to open journal file '{}'!\n{}", l_path.display(), m_error.to_string());
+				return false;
+			}
+		};
+
+		// Separate from earlier entries
+		let l_data = match l_file.metadata().map(|a_meta| a_meta.len()).unwrap_or(0)
+		{
+			0 => l_data,
+			_ => format!("\n{}", l_data),
+		};
+
+		// Write and sync
+		match l_file.write_all(l_data.as_bytes()).and_then(|_| l_file.sync_all())
+		{
+			Ok(_) => return true,
+			Err(m_error) =>
+			{
+				eprintln!("Error: Failed to write journal file '{}'!\n{}", l_path.display(), m_error.to_string());
+				return false;
+			}
+		}
+	}
+
+	/// Checksum
+	///
+	/// SHA-256 of a file, or of the names, link targets and contents
+	/// of a whole directory.
+	pub fn checksum(a_path: &Path) -> io::Result<String>
+	{
+		let mut l_hasher = Sha256::new();
+		Journal::hash(&mut l_hasher, a_path, Path::new(""))?;
+		return Ok(format!("{:x}", l_hasher.finalize()));
+	}
+
+	/// Hash
+	fn hash(a_hasher: &mut Sha256, a_path: &Path, a_name: &Path) -> io::Result<()>
+	{
+		let l_meta = fs::symlink_metadata(a_path)?;
+
+		// Symbolic link
+		if l_meta.file_type().is_symlink()
+		{
+			a_hasher.update(a_name.to_string_lossy().as_bytes());
+			a_hasher.update(fs::read_link(a_path)?.to_string_lossy().as_bytes());
+			return Ok(());
+		}
+
+		// Directory, sorted by name
+		if l_meta.is_dir()
+		{
+			let mut l_items = fs::read_dir(a_path)?
+				.map(|a_item| a_item.map(|a_item| a_item.file_name()))
+				.collect::<io::Result<Vec<_>>>()?
+			;
+			l_items.sort();
+			for i_item in l_items
+			{
+				Journal::hash(a_hasher, &a_path.join(&i_item), &a_name.join(&i_item))?;
+			}
+			return Ok(());
+		}
+
+		// File
+		a_hasher.update(a_name.to_string_lossy().as_bytes());
+		let mut l_file = fs::File::open(a_path)?;
+		let mut l_buffer = vec![0u8; 65536];
+		loop
+		{
+			let l_read = l_file.read(&mut l_buffer)?;
+			if l_read == 0
+			{
+				break;
+			}
+			a_hasher.update(&l_buffer[..l_read]);
+		}
+		return Ok(());
+	}
+
+	/// Load
+	pub fn load(a_path: &Path) -> Option<Journal>
+	{
+		// No journal yet
+		let l_path = Journal::path(a_path);
+		if !l_path.exists()
+		{
+			return Some(Journal::default());
+		}
+
+		// Read file
+		let l_data = match fs::read_to_string(&l_path)
+		{
+			Ok(m_data) => m_data,
+			Err(m_error) =>
+			{
+				eprintln!("Error: Failed to read journal file '{}'!\n{}", l_path.display(), m_error.to_string());
+				return None;
+			}
+		};
+
+		// Deserialize journal
+		match toml::from_str(l_data.as_str())
+		{
+			Ok(m_journal) => return Some(m_journal),
+			Err(m_error) =>
+			{
+				eprintln!("Error: Failed to parse journal file '{}'!\n{}", l_path.display(), m_error.to_string());
+				return None;
+			}
+		}
+	}
+
+	/// Path
+	pub fn path(a_path: &Path) -> PathBuf
+	{
+		return PathBuf::new().join(a_path).join(FILE_JOURNAL);
+	}
+}
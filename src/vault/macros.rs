@@ -0,0 +1,522 @@
+// Use
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::mem;
+use chrono::{DateTime, Utc};
+use chrono::format::{Item, StrftimeItems};
+use crate::vault::time::{Time, Zone};
+
+/// Macros
+pub const MACRO_PATH: &str = "{PATH}";
+pub const MACRO_STAMP: &str = "{STAMP}";
+
+/// Built-in macros, available everywhere
+pub const BUILTIN: [&str; 10] = ["CONFIG", "DATE", "HOST", "NOW", "PATH", "SEQ", "STAMP", "TASK", "TIME", "USER"];
+
+/// Built-in macros, changing from run to run
+pub const RUN: [&str; 5] = ["DATE", "NOW", "SEQ", "STAMP", "TIME"];
+
+/// MacroPart enum
+#[derive(Clone, Debug, PartialEq)]
+pub enum MacroPart
+{
+	/// Literal text
+	Literal(String),
+
+	/// Macro with name and optional argument, like "{STAMP:%Y}"
+	Macro(String, Option<String>),
+}
+
+/// Macros struct
+#[derive(Clone, Debug)]
+pub struct Macros
+{
+	/// Format of the stamp
+	format: String,
+
+	/// Stamp
+	stamp: DateTime<Utc>,
+
+	/// Values of plain macros, like "PATH" or "LAST:db"
+	values: BTreeMap<String, String>,
+
+	/// Variables
+	vars: BTreeMap<String, String>,
+
+	/// Zone
+	zone: Zone,
+}
+
+/// Macros impl
+impl Macros
+{
+	/// Args
+	///
+	/// Evaluates a command line into its arguments, split at the spaces of its text
+	/// and of the variables it uses. The value of any other macro always stays inside
+	/// its argument, even if it contains spaces (e.g. "{FILE}", "{PATH}" or "{LAST}").
+	pub fn args(&self, a_str: &str) -> Vec<String>
+	{
+		let mut l_args = Vec::new();
+		let mut l_arg = String::new();
+		self.args_push(a_str, &mut l_args, &mut l_arg);
+		if !l_arg.is_empty()
+		{
+			l_args.push(l_arg);
+		}
+		return l_args;
+	}
+
+	/// Args push
+	///
+	/// Evaluates a string into the arguments, continuing the current one.
+	fn args_push(&self, a_str: &str, a_args: &mut Vec<String>, a_arg: &mut String)
+	{
+		// Parse
+		let l_parts = match Macros::parse(a_str)
+		{
+			Ok(m_parts) => m_parts,
+			Err(_m_error) => vec![MacroPart::Literal(a_str.to_string())],
+		};
+
+		// Evaluate parts
+		for i_part in l_parts
+		{
+			match i_part
+			{
+				MacroPart::Literal(m_text) =>
+				{
+					for i_char in m_text.chars()
+					{
+						if i_char != ' '
+						{
+							a_arg.push(i_char);
+						}
+						else if !a_arg.is_empty()
+						{
+							a_args.push(mem::take(a_arg));
+						}
+					}
+				},
+				MacroPart::Macro(m_name, Some(m_arg)) if m_name == "var" && self.vars.contains_key(&m_arg) => self.args_push(&self.vars[&m_arg], a_args, a_arg),
+				MacroPart::Macro(m_name, m_arg) => a_arg.push_str(&self.value(&m_name, m_arg.as_deref())),
+			}
+		}
+	}
+
+	/// Check
+	///
+	/// Checks a string for unknown macros, unknown variables and invalid stamp formats,
+	/// allowing the built-in macros and the given extra ones.
+	pub fn check(a_str: &str, a_extra: &[&str], a_vars: &BTreeMap<String, String>) -> Result<(), String>
+	{
+		for i_part in Macros::parse(a_str)?
+		{
+			let (l_name, l_arg) = match i_part
+			{
+				MacroPart::Literal(_) => continue,
+				MacroPart::Macro(m_name, m_arg) => (m_name, m_arg),
+			};
+			let l_known = match (l_name.as_str(), &l_arg)
+			{
+				("ENV", Some(m_arg)) => !m_arg.is_empty(),
+				("var", Some(m_arg)) =>
+				{
+					if !a_vars.contains_key(m_arg)
+					{
+						return Err(format!("unknown variable '{}'", m_arg));
+					}
+					true
+				},
+				("STAMP", Some(m_arg)) =>
+				{
+					if StrftimeItems::new(m_arg).any(|a_item| a_item == Item::Error)
+					{
+						return Err(format!("invalid stamp format '{}'", m_arg));
+					}
+					true
+				},
+				(m_name, None) => BUILTIN.contains(&m_name) || a_extra.contains(&m_name),
+				(m_name, Some(m_arg)) => a_extra.contains(&format!("{}:{}", m_name, m_arg).as_str()),
+			};
+
+			// Unknown macro
+			if !l_known
+			{
+				return match l_arg
+				{
+					Some(m_arg) => Err(format!("unknown macro '{{{}:{}}}'", l_name, m_arg)),
+					None => Err(format!("unknown macro '{{{}}}'", l_name)),
+				};
+			}
+		}
+		return Ok(());
+	}
+
+	/// Cycle
+	///
+	/// Finds variables referencing themselves, directly or through others.
+	/// Returns the names along the first cycle found.
+	pub fn cycle(a_vars: &BTreeMap<String, String>) -> Option<Vec<String>>
+	{
+		let mut l_done = BTreeSet::<String>::new();
+		for i_name in a_vars.keys()
+		{
+			let mut l_path = Vec::<String>::new();
+			if Macros::cycle_visit(a_vars, i_name, &mut l_path, &mut l_done)
+			{
+				return Some(l_path);
+			}
+		}
+		return None;
+	}
+
+	/// Cycle visit
+	fn cycle_visit(a_vars: &BTreeMap<String, String>, a_name: &str, a_path: &mut Vec<String>, a_done: &mut BTreeSet<String>) -> bool
+	{
+		// Back at a variable of the current path
+		if let Some(m_index) = a_path.iter().position(|a_item| a_item == a_name)
+		{
+			a_path.drain(..m_index);
+			a_path.push(a_name.to_string());
+			return true;
+		}
+
+		// Already checked or unknown
+		if a_done.contains(a_name) || !a_vars.contains_key(a_name)
+		{
+			return false;
+		}
+
+		// Visit referenced variables
+		a_path.push(a_name.to_string());
+		for i_part in Macros::parse(&a_vars[a_name]).unwrap_or_default()
+		{
+			if let MacroPart::Macro(m_name, Some(m_arg)) = i_part
+			{
+				if m_name == "var" && Macros::cycle_visit(a_vars, &m_arg, a_path, a_done)
+				{
+					return true;
+				}
+			}
+		}
+		a_path.pop();
+		a_done.insert(a_name.to_string());
+		return false;
+	}
+
+	/// Eval
+	///
+	/// Replaces all macros, unknown ones are kept as they are.
+	pub fn eval(&self, a_str: &str) -> String
+	{
+		// Parse
+		let l_parts = match Macros::parse(a_str)
+		{
+			Ok(m_parts) => m_parts,
+			Err(_m_error) => return a_str.to_string(),
+		};
+
+		// Evaluate parts
+		let mut l_str = String::new();
+		for i_part in l_parts
+		{
+			match i_part
+			{
+				MacroPart::Literal(m_text) => l_str.push_str(&m_text),
+				MacroPart::Macro(m_name, m_arg) => l_str.push_str(&self.value(&m_name, m_arg.as_deref())),
+			}
+		}
+		return l_str;
+	}
+
+	/// Find
+	///
+	/// Finds the first of the given macros in a string, also inside the variables it uses.
+	pub fn find(a_str: &str, a_names: &[&str], a_vars: &BTreeMap<String, String>) -> Option<String>
+	{
+		return Macros::parse(a_str)
+			.ok()?
+			.into_iter()
+			.find_map(|a_part| match a_part
+			{
+				MacroPart::Macro(m_name, Some(m_arg)) if m_name == "var" => Macros::find(a_vars.get(&m_arg)?, a_names, a_vars),
+				MacroPart::Macro(m_name, _) if a_names.contains(&m_name.as_str()) => Some(m_name),
+				_ => None,
+			})
+		;
+	}
+
+	/// Host
+	fn host() -> String
+	{
+		match hostname::get()
+		{
+			Ok(m_host) => return m_host.to_string_lossy().to_string(),
+			Err(_m_error) => return String::new(),
+		}
+	}
+
+	/// Is name
+	///
+	/// Checks whether a string can name a variable, so "{var:NAME}" reads back the same.
+	pub fn is_name(a_str: &str) -> bool
+	{
+		return !a_str.is_empty() && a_str.chars().all(|a_char| a_char.is_ascii_alphanumeric() || a_char == '_' || a_char == '-');
+	}
+
+	/// New
+	pub fn new(a_stamp: &DateTime<Utc>, a_format: &str, a_zone: &Zone) -> Macros
+	{
+		return Macros
+		{
+			format: a_format.to_string(),
+			stamp: *a_stamp,
+			values: BTreeMap::new(),
+			vars: BTreeMap::new(),
+			zone: *a_zone,
+		};
+	}
+
+	/// Parse
+	///
+	/// Splits a string into literals and macros.
+	/// "{{" and "}}" are literal braces.
+	pub fn parse(a_str: &str) -> Result<Vec<MacroPart>, String>
+	{
+		let mut l_parts = Vec::<MacroPart>::new();
+		let mut l_text = String::new();
+		let mut l_chars = a_str.chars().peekable();
+		while let Some(l_char) = l_chars.next()
+		{
+			// Literal closing brace
+			if l_char == '}'
+			{
+				l_chars.next_if_eq(&'}');
+				l_text.push('}');
+				continue;
+			}
+
+			// Literal
+			if l_char != '{'
+			{
+				l_text.push(l_char);
+				continue;
+			}
+
+			// Literal opening brace
+			if l_chars.next_if_eq(&'{').is_some()
+			{
+				l_text.push('{');
+				continue;
+			}
+
+			// Get macro
+			let mut l_macro = String::new();
+			let mut l_closed = false;
+			for l_next in l_chars.by_ref()
+			{
+				if l_next == '}'
+				{
+					l_closed = true;
+					break;
+				}
+				l_macro.push(l_next);
+			}
+			if !l_closed
+			{
+				return Err(format!("unclosed macro '{{{}'", l_macro));
+			}
+
+			// Add parts
+			if !l_text.is_empty()
+			{
+				l_parts.push(MacroPart::Literal(l_text.clone()));
+				l_text.clear();
+			}
+			l_parts.push(match l_macro.split_once(':')
+			{
+				Some((m_name, m_arg)) => MacroPart::Macro(m_name.to_string(), Some(m_arg.to_string())),
+				None => MacroPart::Macro(l_macro, None),
+			});
+		}
+		if !l_text.is_empty()
+		{
+			l_parts.push(MacroPart::Literal(l_text));
+		}
+		return Ok(l_parts);
+	}
+
+	/// Set
+	pub fn set(&mut self, a_name: &str, a_value: &str) -> &mut Macros
+	{
+		self.values.insert(a_name.to_string(), a_value.to_string());
+		return self;
+	}
+
+	/// Set var
+	///
+	/// Sets a variable to a literal value, like the captured output of a command.
+	pub fn set_var(&mut self, a_name: &str, a_value: &str) -> &mut Macros
+	{
+		self.vars.insert(a_name.to_string(), a_value.replace('{', "{{").replace('}', "}}"));
+		return self;
+	}
+
+	/// Set vars
+	pub fn set_vars(&mut self, a_vars: &BTreeMap<String, String>) -> &mut Macros
+	{
+		self.vars = a_vars.clone();
+		return self;
+	}
+
+	/// User
+	fn user() -> String
+	{
+		for i_name in ["USER", "LOGNAME", "USERNAME"]
+		{
+			if let Ok(m_user) = env::var(i_name)
+			{
+				return m_user;
+			}
+		}
+		return String::new();
+	}
+
+	/// Value
+	fn value(&self, a_name: &str, a_arg: Option<&str>) -> String
+	{
+		match (a_name, a_arg)
+		{
+			("DATE", None) => return Time::format(&Time::now(), "%Y-%m-%d", &self.zone),
+			("ENV", Some(m_arg)) => return env::var(m_arg).unwrap_or_default(),
+			("HOST", None) => return Macros::host(),
+			("NOW", None) => return Time::to_string(&Time::now()),
+			("STAMP", None) => return Time::format(&self.stamp, &self.format, &self.zone),
+			("STAMP", Some(m_arg)) => return Time::format(&self.stamp, m_arg, &self.zone),
+			("TIME", None) => return Time::format(&Time::now(), "%H-%M-%S", &self.zone),
+			("USER", None) => return Macros::user(),
+			("var", Some(m_arg)) if self.vars.contains_key(m_arg) => return self.eval(&self.vars[m_arg]),
+			(m_name, None) if self.values.contains_key(m_name) => return self.values[m_name].clone(),
+			(m_name, Some(m_arg)) => match self.values.get(&format!("{}:{}", m_name, m_arg))
+			{
+				Some(m_value) => return m_value.clone(),
+				None => return format!("{{{}:{}}}", m_name, m_arg),
+			},
+			(m_name, None) => return format!("{{{}}}", m_name),
+		}
+	}
+}
+
+/// Tests mod
+mod tests
+{
+	/// Args
+	#[test]
+	fn args()
+	{
+		use chrono::{TimeZone, Utc};
+		use crate::vault::macros::Macros as Macros;
+		use crate::vault::time::Zone as Zone;
+		let l_stamp = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+		let mut l_macros = Macros::new(&l_stamp, "%Y%m%d", &Zone::Tz(chrono_tz::UTC));
+		l_macros.set("FILE", "/backup/my db.sql").set_var("opts", "-a  -b").set_var("flags", "-n -e");
+		assert_eq!(l_macros.args("to-tape  {FILE} {STAMP}.log"), vec!["to-tape", "/backup/my db.sql", "20240102.log"]);
+		assert_eq!(l_macros.args("cmd {var:opts} x{{y}}"), vec!["cmd", "-a", "-b", "x{y}"]);
+		assert_eq!(l_macros.args("printf [%s]\\n {var:flags} x"), vec!["printf", "[%s]\\n", "-n", "-e", "x"]);
+		assert_eq!(l_macros.args(" "), Vec::<String>::new());
+
+		// Variables are split at their spaces, the macros inside them are not
+		l_macros.set_vars(&std::collections::BTreeMap::from([(String::from("in"), String::from("--in={FILE} -v"))]));
+		assert_eq!(l_macros.args("cmd {var:in}x"), vec!["cmd", "--in=/backup/my db.sql", "-vx"]);
+	}
+
+	/// Check
+	#[test]
+	fn check()
+	{
+		use std::collections::BTreeMap;
+		use crate::vault::macros::Macros as Macros;
+		let l_vars = BTreeMap::from([(String::from("src"), String::from("/srv"))]);
+		assert!(Macros::check("tar czf {STAMP}.tgz {PATH}/{ENV:HOME} {STAMP:%Y%m%d}", &[], &l_vars).is_ok());
+		assert!(Macros::check("mv {FILE} /tape/{BUCKET}", &["BUCKET", "FILE"], &l_vars).is_ok());
+		assert!(Macros::check("mv {FILE} /tape", &[], &l_vars).is_err());
+		assert!(Macros::check("echo {STAMP:%Q}", &[], &l_vars).is_err());
+		assert!(Macros::check("echo {STAMP", &[], &l_vars).is_err());
+		assert!(Macros::check("echo {{FILE}}", &[], &l_vars).is_ok());
+		assert!(Macros::check("tar czf x.tgz {var:src}", &[], &l_vars).is_ok());
+		assert!(Macros::check("tar czf x.tgz {var:dst}", &[], &l_vars).is_err());
+	}
+
+	/// Find
+	#[test]
+	fn find()
+	{
+		use std::collections::BTreeMap;
+		use crate::vault::macros::{Macros as Macros, RUN as RUN};
+		let l_vars = BTreeMap::from([(String::from("day"), String::from("{STAMP:%d}")), (String::from("src"), String::from("/srv"))]);
+		assert_eq!(Macros::find("/mnt/{STAMP}", &RUN, &l_vars), Some(String::from("STAMP")));
+		assert_eq!(Macros::find("/mnt/{var:day}", &RUN, &l_vars), Some(String::from("STAMP")));
+		assert_eq!(Macros::find("/mnt/{HOST}/{var:src}", &RUN, &l_vars), None);
+		assert_eq!(Macros::find("/mnt/{{STAMP}}", &RUN, &l_vars), None);
+	}
+
+	/// Cycle
+	#[test]
+	fn cycle()
+	{
+		use std::collections::BTreeMap;
+		use crate::vault::macros::Macros as Macros;
+		let mut l_vars = BTreeMap::from(
+		[
+			(String::from("a"), String::from("{var:b}/x")),
+			(String::from("b"), String::from("{HOST}-{var:c}")),
+			(String::from("c"), String::from("c")),
+		]);
+		assert_eq!(Macros::cycle(&l_vars), None);
+		l_vars.insert(String::from("c"), String::from("{var:a}"));
+		assert_eq!(Macros::cycle(&l_vars), Some(vec![String::from("a"), String::from("b"), String::from("c"), String::from("a")]));
+	}
+
+	/// Eval
+	#[test]
+	fn eval()
+	{
+		use chrono::{TimeZone, Utc};
+		use crate::vault::macros::Macros as Macros;
+		use crate::vault::time::Zone as Zone;
+		let l_stamp = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+		let mut l_macros = Macros::new(&l_stamp, "%Y-%m-%d_%H-%M-%S", &Zone::Tz(chrono_tz::UTC));
+		l_macros.set("PATH", "/backup").set("SEQ", "7");
+		assert_eq!(l_macros.eval("{PATH}/{STAMP}-{SEQ}.tgz"), "/backup/2024-01-02_03-04-05-7.tgz");
+		assert_eq!(l_macros.eval("{STAMP:%Y%m%d}"), "20240102");
+		assert_eq!(l_macros.eval("awk '{{print $1}}' {UNKNOWN}"), "awk '{print $1}' {UNKNOWN}");
+	}
+
+	/// Is name
+	#[test]
+	fn is_name()
+	{
+		use crate::vault::macros::Macros as Macros;
+		assert!(Macros::is_name("snapshot_id"));
+		assert!(Macros::is_name("db-1"));
+		assert!(!Macros::is_name(""));
+		assert!(!Macros::is_name("snap id"));
+		assert!(!Macros::is_name("a}b"));
+		assert!(!Macros::is_name("a:b"));
+	}
+
+	/// Set var
+	#[test]
+	fn set_var()
+	{
+		use chrono::{TimeZone, Utc};
+		use crate::vault::macros::Macros as Macros;
+		use crate::vault::time::Zone as Zone;
+		let l_stamp = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+		let mut l_macros = Macros::new(&l_stamp, "%Y%m%d", &Zone::Tz(chrono_tz::UTC));
+		l_macros.set_var("out", "{STAMP} {{x}} }{");
+		assert_eq!(l_macros.eval("id={var:out}"), "id={STAMP} {{x}} }{");
+		assert_eq!(l_macros.args("echo {var:out}"), vec!["echo", "{STAMP}", "{{x}}", "}{"]);
+	}
+}
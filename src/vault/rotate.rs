@@ -1,25 +1,114 @@
 // Use
-use regex::Regex;
-use std::collections::BTreeMap;
+use chrono::{DateTime, Datelike, FixedOffset, TimeZone, Utc};
+use regex::{escape, Regex};
+use serde::Serialize;
+use serde_json::json;
+use filetime::FileTime;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
 use std::vec::Vec;
+use xz2::write::XzEncoder;
+use crate::vault::config::ConfigTaskArtifact;
+use crate::vault::journal::{FILE_JOURNAL, Journal, JournalEntry};
+use crate::vault::state::{FILE_STATE, FILE_STATE_LOCK, MARKER_KEEP, State};
+use crate::vault::macros::MACRO_STAMP;
 use crate::vault::task::Task;
+use crate::vault::time::Time;
+
+// Archive extension
+pub const ARCHIVE_EXTENSION: &str = ".tar.zst";
+
+// Archive index
+pub const ARCHIVE_INDEX: &str = ".tar.zst.index.toml";
 
 // Directory move
 pub const DIRECTORY_MOVE: &str = "moved";
 
-// Regular expressions
-pub const REGEXP_DAILY: &str = "^([0-9]{4}-[0-9]{2}-[0-9]{2})";
-pub const REGEXP_HOURLY: &str = "^([0-9]{4}-[0-9]{2}-[0-9]{2}_[0-9]{2})";
-pub const REGEXP_MONTHLY: &str = "^([0-9]{4}-[0-9]{2})";
-pub const REGEXP_YEARLY: &str = "^([0-9]{4})";
+// Excluded entries of a task path, owned by Vault itself
+pub const EXCLUDED: [&str; 4] = [DIRECTORY_MOVE, FILE_JOURNAL, FILE_STATE, FILE_STATE_LOCK];
+
+// Excluded suffixes, markers and unfinished transfers
+pub const EXCLUDED_SUFFIXES: [&str; 2] = [MARKER_KEEP, ".part"];
+
+// Bucket function type
+pub type RotateBucket = fn(&DateTime<FixedOffset>) -> String;
+
+// RotateAction struct
+#[derive(Clone, Debug, Serialize)]
+pub struct RotateAction
+{
+	/// Bucket
+	pub bucket: String,
+
+	/// File
+	pub file: String,
+
+	/// Keep
+	pub keep: bool,
+
+	/// Pinned
+	pub pinned: bool,
+
+	/// Rule
+	pub rule: String,
+}
+
+// RotateArchive struct
+#[derive(Clone, Debug, Serialize)]
+pub struct RotateArchive
+{
+	/// Bucket
+	pub bucket: String,
+
+	/// Created
+	pub created: String,
+
+	/// Files
+	pub files: Vec<RotateArchiveEntry>,
+}
+
+// RotateArchiveEntry struct
+#[derive(Clone, Debug, Serialize)]
+pub struct RotateArchiveEntry
+{
+	/// Name
+	pub name: String,
+
+	/// Size
+	pub size: u64,
+}
+
+// RotateFile struct
+#[derive(Clone, Debug)]
+pub struct RotateFile
+{
+	/// Name
+	pub name: String,
+
+	/// Pinned
+	pub pinned: bool,
+
+	/// Time
+	pub time: DateTime<Utc>,
+}
 
 // Rotate struct
 pub struct Rotate
 {
+	/// Artifact
+	artifact: ConfigTaskArtifact,
+
 	// Operate function
-	operate: fn(&Rotate, &str) -> bool,
+	operate: fn(&Rotate, &str, &str, &[String], &str) -> bool,
+
+	/// Target
+	target: PathBuf,
 
 	/// Task
 	task: Task,
@@ -28,15 +117,179 @@ pub struct Rotate
 // Rotate impl
 impl Rotate
 {
+	/// Action
+	///
+	/// Pinned files are always kept, even if the rule would rotate them.
+	fn action(a_bucket: &str, a_file: &RotateFile, a_keep: bool, a_rule: &str) -> RotateAction
+	{
+		return RotateAction
+		{
+			bucket: a_bucket.to_string(),
+			file: a_file.name.clone(),
+			keep: a_keep || a_file.pinned,
+			pinned: !a_keep && a_file.pinned,
+			rule: a_rule.to_string(),
+		};
+	}
+
+	/// Archive
+	///
+	/// Writes files into a zstd compressed tarball.
+	fn archive(&self, a_archive: &Path, a_files: &[String], a_entries: &mut Vec<RotateArchiveEntry>) -> io::Result<()>
+	{
+		return Rotate::tarball(a_archive, "zstd", |a_builder|
+		{
+			a_builder.follow_symlinks(false);
+			for i_file in a_files
+			{
+				let l_path = PathBuf::new().join(self.task.task.path.clone()).join(i_file);
+				match fs::symlink_metadata(&l_path)?.is_dir()
+				{
+					true => a_builder.append_dir_all(i_file, &l_path)?,
+					false => a_builder.append_path_with_name(&l_path, i_file)?,
+				}
+				a_entries.push(RotateArchiveEntry
+				{
+					name: i_file.clone(),
+					size: self.file_size(i_file),
+				});
+			}
+			return Ok(());
+		});
+	}
+
+	/// Archive name
+	///
+	/// Gets a unique name for the archive of a bucket, neither archive nor index may exist.
+	/// Files of rules without buckets are named after the rule and the time of rotation.
+	fn archive_name(&self, a_rule: &str, a_bucket: &str) -> String
+	{
+		// Get base name
+		let l_base = match a_bucket.is_empty()
+		{
+			true => format!("{}-{}", a_rule, Time::format(&Time::now(), &self.task.stamp_format(), &self.task.cfg.get_zone())),
+			false => a_bucket.to_string(),
+		};
+
+		// Get unique name
+		let mut l_name = l_base.clone();
+		let mut l_number: usize = 0;
+		while [ARCHIVE_EXTENSION, ARCHIVE_INDEX].iter().any(|a_extension| fs::symlink_metadata(self.target.join(l_name.clone() + a_extension)).is_ok())
+		{
+			l_number += 1;
+			l_name = format!("{}-{}", l_base, l_number);
+		}
+		return l_name;
+	}
+
+	/// Bucket daily
+	fn bucket_daily(a_time: &DateTime<FixedOffset>) -> String
+	{
+		return a_time.format("%Y-%m-%d").to_string();
+	}
+
+	/// Bucket hourly
+	fn bucket_hourly(a_time: &DateTime<FixedOffset>) -> String
+	{
+		return a_time.format("%Y-%m-%d_%H").to_string();
+	}
+
+	/// Bucket monthly
+	fn bucket_monthly(a_time: &DateTime<FixedOffset>) -> String
+	{
+		return a_time.format("%Y-%m").to_string();
+	}
+
+	/// Bucket weekly
+	fn bucket_weekly(a_time: &DateTime<FixedOffset>) -> String
+	{
+		let l_week = a_time.iso_week();
+		return format!("{:04}-W{:02}", l_week.year(), l_week.week());
+	}
+
+	/// Bucket yearly
+	fn bucket_yearly(a_time: &DateTime<FixedOffset>) -> String
+	{
+		return a_time.format("%Y").to_string();
+	}
+
+	/// Copy
+	///
+	/// Copies a file or a whole directory, syncing every copied file.
+	pub fn copy(a_source: &Path, a_target: &Path) -> io::Result<()>
+	{
+		let l_meta = fs::symlink_metadata(a_source)?;
+
+		// Symbolic link
+		if l_meta.file_type().is_symlink()
+		{
+			return Rotate::copy_symlink(a_source, a_target);
+		}
+
+		// Directory
+		if l_meta.is_dir()
+		{
+			fs::create_dir(a_target)?;
+			for i_item in fs::read_dir(a_source)?
+			{
+				let l_item = i_item?;
+				Rotate::copy(&l_item.path(), &a_target.join(l_item.file_name()))?;
+			}
+			return Ok(());
+		}
+
+		// File
+		return Rotate::copy_file(a_source, a_target);
+	}
+
+	/// Copy file
+	///
+	/// Copies a file with its permissions and modification time, synced through
+	/// a handle opened for writing, as read-only handles can not sync on every system.
+	pub fn copy_file(a_source: &Path, a_target: &Path) -> io::Result<()>
+	{
+		let l_meta = fs::metadata(a_source)?;
+		let mut l_file = OpenOptions::new().write(true).create(true).truncate(true).open(a_target)?;
+		io::copy(&mut File::open(a_source)?, &mut l_file)?;
+		filetime::set_file_handle_times(&l_file, None, Some(FileTime::from_last_modification_time(&l_meta)))?;
+		l_file.set_permissions(l_meta.permissions())?;
+		return l_file.sync_all();
+	}
+
+	/// Copy symlink
+	///
+	/// Copies a symbolic link itself, keeping its target as it is.
+	pub fn copy_symlink(a_source: &Path, a_target: &Path) -> io::Result<()>
+	{
+		let l_link = fs::read_link(a_source)?;
+		#[cfg(unix)]
+		{
+			return std::os::unix::fs::symlink(l_link, a_target);
+		}
+		#[cfg(windows)]
+		{
+			match fs::metadata(a_source).map(|a_meta| a_meta.is_dir()).unwrap_or(false)
+			{
+				true => return std::os::windows::fs::symlink_dir(l_link, a_target),
+				false => return std::os::windows::fs::symlink_file(l_link, a_target),
+			}
+		}
+		#[cfg(not(any(unix, windows)))]
+		{
+			let _ = (l_link, a_target);
+			return Err(io::Error::other("symbolic links can not be copied"));
+		}
+	}
+
 	/// File delete
 	fn file_delete(&self, a_file: &str) -> bool
 	{
-		match fs::remove_file(PathBuf::new().join(self.task.task.path.clone()).join(a_file))
+		match Rotate::remove(&PathBuf::new().join(self.task.task.path.clone()).join(a_file))
 		{
 			Ok(_) => return true,
 			Err(m_error) =>
 			{
-				println!("Error: {}.{} failed to delete file '{}'!\n{}", self.task.cfg.name, self.task.name, a_file, m_error.to_string());
+				eprintln!("Error: {}.{} failed to delete file '{}'!\n{}", self.task.cfg.name, self.task.name, a_file, m_error.to_string());
 				return false;
 			},
 		}
@@ -46,35 +299,159 @@ impl Rotate
 	fn file_move(&self, a_file: &str) -> bool
 	{
 		let l_source = PathBuf::new().join(self.task.task.path.clone()).join(a_file);
-		let l_target = PathBuf::new().join(self.task.task.path.clone()).join(DIRECTORY_MOVE).join(a_file);
-		match fs::rename(l_source, l_target)
+		let l_target = self.target.join(a_file);
+		match Rotate::transfer(&l_source, &l_target)
 		{
 			Ok(_) => return true,
 			Err(m_error) =>
 			{
-				println!("Error: {}.{} failed to move file '{}' into '{}/{}'!\n{}", self.task.cfg.name, self.task.name, a_file, DIRECTORY_MOVE, a_file, m_error.to_string());
+				eprintln!("Error: {}.{} failed to move file '{}' into '{}'!\n{}", self.task.cfg.name, self.task.name, a_file, l_target.display(), m_error.to_string());
 				return false;
 			}
 		}
 	}
 
-	/// File unknown
-	fn file_unknown(&self, _a_file: &str) -> bool
+	/// File size
+	fn file_size(&self, a_file: &str) -> u64
 	{
-		// Always return false
-		return false;
+		return Rotate::size(&PathBuf::new().join(self.task.task.path.clone()).join(a_file));
+	}
+
+	/// File time
+	///
+	/// Gets the modification or change time of a file, depending on the rotate source.
+	/// Where no change time exists, the creation time is used instead.
+	fn file_time(&self, a_file: &str) -> Option<DateTime<Utc>>
+	{
+		// Get metadata
+		let l_meta = match fs::symlink_metadata(PathBuf::new().join(self.task.task.path.clone()).join(a_file))
+		{
+			Ok(m_meta) => m_meta,
+			Err(m_error) =>
+			{
+				eprintln!("Error: {}.{} failed to read metadata of file '{}'!\n{}", self.task.cfg.name, self.task.name, a_file, m_error.to_string());
+				return None;
+			}
+		};
+
+		// Modification time
+		if self.artifact.rotate_source == "mtime"
+		{
+			return match l_meta.modified()
+			{
+				Ok(m_time) => Some(DateTime::<Utc>::from(m_time)),
+				Err(_m_error) => None,
+			};
+		}
+
+		// Change time
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::MetadataExt;
+			return Utc.timestamp_opt(l_meta.ctime(), l_meta.ctime_nsec() as u32).single();
+		}
+
+		// Creation time
+		#[cfg(not(unix))]
+		{
+			return match l_meta.created()
+			{
+				Ok(m_time) => Some(DateTime::<Utc>::from(m_time)),
+				Err(_m_error) => None,
+			};
+		}
+	}
+
+	/// Guard
+	///
+	/// Checks a plan against "max_rotate_count" and "max_rotate_fraction",
+	/// so a wrong stamp format or a clock jump can not wipe out the backups.
+	fn guard(&self, a_plan: &[RotateAction]) -> bool
+	{
+		// Count files to rotate and existing files
+		let l_count = a_plan.iter().filter(|a_action| !a_action.keep).count();
+		let l_total = a_plan.iter().map(|a_action| a_action.file.as_str()).collect::<BTreeSet<&str>>().len();
+
+		// Too many files
+		let l_max = self.task.task.max_rotate_count;
+		if l_max > 0 && l_count as u64 > l_max
+		{
+			eprintln!("Error: {}.{} would rotate {} of {} {}, more than max rotate count {} (use --allow-mass-rotate)!", self.task.cfg.name, self.task.name, l_count, l_total, Rotate::word(l_total), l_max);
+			return false;
+		}
+
+		// Too large share of files
+		let l_fraction = self.task.task.max_rotate_fraction;
+		if l_fraction > 0.0 && l_count as f64 > l_total as f64 * l_fraction
+		{
+			eprintln!("Error: {}.{} would rotate {} of {} {}, more than max rotate fraction {} (use --allow-mass-rotate)!", self.task.cfg.name, self.task.name, l_count, l_total, Rotate::word(l_total), l_fraction);
+			return false;
+		}
+
+		// Done
+		return true;
+	}
+
+	/// Is excluded
+	///
+	/// Checks whether an entry of the task path is one of Vault's own, never rotated or archived.
+	pub fn is_excluded(a_file: &str) -> bool
+	{
+		return EXCLUDED.contains(&a_file) || EXCLUDED_SUFFIXES.iter().any(|a_suffix| a_file.ends_with(a_suffix));
+	}
+
+	/// Journal
+	///
+	/// Describes a file about to be rotated for the journal,
+	/// with the file or archive it becomes in the move target.
+	fn journal(&self, a_run: &str, a_rule: &str, a_bucket: &str, a_file: &str, a_archive: &str) -> JournalEntry
+	{
+		let l_path = PathBuf::new().join(self.task.task.path.clone()).join(a_file);
+		return JournalEntry
+		{
+			bucket: a_bucket.to_string(),
+			checksum: Journal::checksum(&l_path).unwrap_or_default(),
+			file: a_file.to_string(),
+			rule: a_rule.to_string(),
+			run: a_run.to_string(),
+			size: Rotate::size(&l_path),
+			strategy: self.task.task.rotate_strategy.clone(),
+			target: match self.task.task.rotate_strategy.as_str()
+			{
+				"archive" => self.target.join(a_archive.to_string() + ARCHIVE_EXTENSION).to_string_lossy().to_string(),
+				"move" => self.target.join(a_file).to_string_lossy().to_string(),
+				_ => String::new(),
+			},
+			task: format!("{}.{}", self.task.cfg.name, self.task.name),
+			time: Time::to_string(&Time::now()),
+		};
+	}
+
+	/// Last
+	///
+	/// Gets the newest backup of the artifact, if there is one.
+	pub fn last(&self) -> Option<RotateFile>
+	{
+		return self.list_stamped()?.into_iter().next();
 	}
 
 	/// List files
 	fn list_files(&self) -> Vec<String>
 	{
 		let mut l_list = Vec::<String>::new();
+
+		// Path not created yet
+		if !self.task.task.path.exists()
+		{
+			return l_list;
+		}
+
 		let l_items = match fs::read_dir(self.task.task.path.clone())
 		{
 			Ok(m_items) => m_items,
 			Err(m_error) =>
 			{
-				println!("Error: {}.{} failed to read directory '{}'!\n{}", self.task.cfg.name, self.task.name, self.task.task.path.display(), m_error.to_string());
+				eprintln!("Error: {}.{} failed to read directory '{}'!\n{}", self.task.cfg.name, self.task.name, self.task.task.path.display(), m_error.to_string());
 				return l_list;
 			},
 		};
@@ -91,6 +468,18 @@ impl Rotate
 						None => continue,
 					};
 
+					// Skip own entries
+					if Rotate::is_excluded(&l_file)
+					{
+						continue;
+					}
+
+					// Skip move target inside path
+					if self.target.starts_with(self.task.task.path.join(&l_file))
+					{
+						continue;
+					}
+
 					l_list.push(l_file);
 				},
 				Err(_m_error) => {},
@@ -100,193 +489,655 @@ impl Rotate
 		return l_list;
 	}
 
-	/// List filters
-	fn list_filters(&self, a_list: &Vec<String>, a_filter: &str) -> Vec<String>
+	/// List stamped
+	pub fn list_stamped(&self) -> Option<Vec<RotateFile>>
 	{
-		let mut l_list = Vec::<String>::new();
-		for i_item in a_list
-		{
-			if i_item.starts_with(a_filter)
-			{
-				l_list.push(i_item.clone());
-			}
-		}
-		return l_list;
-	}
+		// Create list
+		let mut l_list = Vec::<RotateFile>::new();
 
-	/// List tree
-	fn list_tree(&self, a_expr: &str) -> Option<BTreeMap::<String, Vec<String>>>
-	{
-		// Create tree
-		let mut l_tree = BTreeMap::<String, Vec<String>>::new();
+		// Get pinned files
+		let l_pinned = match State::load(&self.task.task.path)
+		{
+			Some(m_state) => m_state.pinned,
+			None => return None,
+		};
 
-		// Get list of files
-		let l_files = self.list_files();
+		// Get glob
+		let l_glob = match self.regex(&format!("^{}$", Rotate::wildcard(&self.artifact.rotate_glob)))
+		{
+			Some(m_regex) => m_regex,
+			None => return None,
+		};
 
-		// List is empty
-		if l_files.is_empty()
+		// Get pattern
+		let l_regex = match self.pattern()
 		{
-			return Some(l_tree);
-		}
+			Some(m_regex) => m_regex,
+			None => return None,
+		};
 
-		// Create regular expression
-		let l_regex = match Regex::new(a_expr)
+		// Iterate over files
+		for i_file in self.list_files()
 		{
-			Ok(m_regex) => m_regex,
-			Err(m_error) =>
+			// Not matching glob
+			if !l_glob.is_match(&i_file)
 			{
-				println!("Error: {}.{} invalid regular expression '{}'!\n{}", self.task.cfg.name, self.task.name, a_expr, m_error.to_string());
-				return None;
+				continue;
 			}
-		};
 
-		// Get filters
-		let l_filters = self.list_uniques(&l_files, &l_regex);
+			// Get time from rotate source
+			let l_time = match self.artifact.rotate_source.as_str()
+			{
+				// Time from file name
+				"name" => match l_regex.captures(&i_file)
+				{
+					Some(m_match) => Time::parse(&m_match[1], &self.task.task.stamp_format, &self.task.cfg.get_zone()),
+					None => None,
+				},
 
-		// Iterate over filters
-		for i_filter in l_filters
-		{
-			// Insert filtered file list into tree
-			l_tree.insert(i_filter.clone(), self.list_filters(&l_files, &i_filter));
-		}
+				// Time from file system
+				"ctime" | "mtime" => self.file_time(&i_file),
 
-		// Done
-		return Some(l_tree);
-	}
+				// Unknown
+				_ =>
+				{
+					eprintln!("Error: {}.{} unknown rotate source '{}'!", self.task.cfg.name, self.task.name, self.artifact.rotate_source);
+					return None;
+				},
+			};
 
-	/// List uniques
-	fn list_uniques(&self, a_list: &Vec<String>, a_regex: &Regex) -> Vec<String>
-	{
-		let mut l_list = Vec::<String>::new();
-		for i_item in a_list
-		{
-			match a_regex.captures(i_item)
+			// No time
+			let l_time = match l_time
 			{
-				Some(m_match) => l_list.push(m_match[1].to_string()),
-				None => {},
+				Some(m_time) => m_time,
+				None => continue,
 			};
+
+			// Pinned in state or by marker
+			let l_pinned = l_pinned.contains(&i_file) || PathBuf::new().join(self.task.task.path.clone()).join(i_file.clone() + MARKER_KEEP).exists();
+
+			// Add to list
+			l_list.push(RotateFile
+			{
+				name: i_file,
+				pinned: l_pinned,
+				time: l_time,
+			});
 		}
-		l_list.dedup();
-		return l_list;
+
+		// Sort by time, newest first
+		l_list.sort_by(|a_left, a_right| a_right.time.cmp(&a_left.time).then(a_right.name.cmp(&a_left.name)));
+
+		// Done
+		return Some(l_list);
 	}
 
 	// New
-	pub fn new(a_task: &Task) -> Rotate
+	pub fn new(a_task: &Task, a_artifact: &ConfigTaskArtifact) -> Rotate
 	{
 		return Rotate
 		{
+			artifact: a_artifact.clone(),
 			operate: match a_task.task.rotate_strategy.as_str()
 			{
-				"delete" => Rotate::file_delete,
-				"move" => Rotate::file_move,
-				_ => Rotate::file_unknown,
+				"archive" => Rotate::operate_archive,
+				"command" => Rotate::operate_command,
+				"delete" => Rotate::operate_delete,
+				"move" => Rotate::operate_move,
+				_ => Rotate::operate_unknown,
 			},
+			target: a_task.target(),
 			task: a_task.clone()
 		};
 	}
 
-	/// Rotate
-	fn rotate(&self, a_expr: &str, a_verb: &str) -> bool
+	/// Normalize
+	///
+	/// Resolves "." and ".." of a path by its text alone, so paths not existing yet compare.
+	pub fn normalize(a_path: &Path) -> PathBuf
 	{
-		// Get tree
-		let mut l_tree = match self.list_tree(a_expr)
-		{
-			Some(m_map) => m_map,
-			None => return false,
-		};
-
-		// Tree is empty
-		if l_tree.is_empty()
+		let mut l_path = PathBuf::new();
+		for i_component in a_path.components()
 		{
-			return true;
+			match i_component
+			{
+				Component::CurDir => {},
+				Component::ParentDir if matches!(l_path.components().next_back(), Some(Component::Normal(_))) => { l_path.pop(); },
+				_ => l_path.push(i_component),
+			}
 		}
+		return l_path;
+	}
 
-		// Remove last entry
-		l_tree.pop_last();
+	/// Operate archive
+	///
+	/// Bundles the files of a bucket into a compressed tarball with an index.
+	fn operate_archive(&self, a_rule: &str, a_bucket: &str, a_files: &[String], a_archive: &str) -> bool
+	{
+		// Get paths
+		let l_archive = self.target.join(a_archive.to_string() + ARCHIVE_EXTENSION);
+		let l_index = self.target.join(a_archive.to_string() + ARCHIVE_INDEX);
 
-		// Debug
-		if self.task.cfg.debug
+		// Create archive
+		let mut l_entries = Vec::<RotateArchiveEntry>::new();
+		match self.archive(&l_archive, a_files, &mut l_entries)
 		{
-			dbg!(a_expr);
-			dbg!(l_tree.clone());
+			Ok(_) => {},
+			Err(m_error) =>
+			{
+				let _ = fs::remove_file(PathBuf::from(format!("{}.part", l_archive.display())));
+				eprintln!("Error: {}.{} failed to create archive '{}'!\n{}", self.task.cfg.name, self.task.name, l_archive.display(), m_error.to_string());
+				return false;
+			},
 		}
 
-		// Create counter
-		let mut l_count: usize = 0;
-
-		// Iterate over tree
-		for (_i_k, i_v) in &l_tree
+		// Create index
+		let l_data = match toml::to_string(&RotateArchive
+		{
+			bucket: a_archive.to_string(),
+			created: Time::to_string(&Time::now()),
+			files: l_entries,
+		})
 		{
-			// No files
-			if i_v.is_empty()
+			Ok(m_data) => m_data,
+			Err(m_error) =>
 			{
-				continue;
-			}
+				eprintln!("Error: {}.{} failed to construct archive index '{}'!\n{}", self.task.cfg.name, self.task.name, l_index.display(), m_error.to_string());
+				return false;
+			},
+		};
 
-			// Already rotated
-			if i_v.len() == 1
+		// Write index
+		match fs::write(&l_index, l_data)
+		{
+			Ok(_) => {},
+			Err(m_error) =>
 			{
-				continue;
-			}
+				eprintln!("Error: {}.{} failed to write archive index '{}'!\n{}", self.task.cfg.name, self.task.name, l_index.display(), m_error.to_string());
+				return false;
+			},
+		}
+
+		// Delete archived files
+		return self.operate_delete(a_rule, a_bucket, a_files, a_archive);
+	}
 
-			// Clone file list
-			let mut l_files = i_v.clone();
+	/// Operate command
+	///
+	/// Hands every file over to the configured rotate command.
+	/// "{BUCKET}" is the bucket, or the rule for rules without buckets.
+	fn operate_command(&self, a_rule: &str, a_bucket: &str, a_files: &[String], _a_archive: &str) -> bool
+	{
+		// Get macros
+		let l_path = self.task.task.path.clone();
+		let mut l_macros = self.task.macros(&Time::now());
+		l_macros.set("BUCKET", match a_bucket.is_empty()
+		{
+			true => a_rule,
+			false => a_bucket,
+		});
 
-			// Remove last entry
-			l_files.pop();
+		// Iterate over files
+		for i_file in a_files
+		{
+			// Eval command
+			l_macros.set("FILE", &l_path.join(i_file).to_string_lossy());
+			let l_args = l_macros.args(&self.task.task.rotate_command);
 
-			// Iterate over file list
-			for i_file in &l_files
+			// Execute command
+			if !self.task.execute(&l_args, &l_path, None)
 			{
-				// Operate on that file
-				if !(self.operate)(&self, i_file)
-				{
-					return false;
-				}
-				l_count += 1;
+				return false;
 			}
 		}
+		return true;
+	}
 
-		// Display rotation status
-		if l_count > 0
+	/// Operate delete
+	fn operate_delete(&self, _a_rule: &str, _a_bucket: &str, a_files: &[String], _a_archive: &str) -> bool
+	{
+		for i_file in a_files
 		{
-			let l_word = match l_count
+			if !self.file_delete(i_file)
 			{
-				1 => "file",
-				_ => "files",
-			};
-			println!("{}.{} rotate {} {}d {} {}.", self.task.cfg.name, self.task.name, a_verb, self.task.task.rotate_strategy, l_count, l_word);
+				return false;
+			}
 		}
-
-		// Done
 		return true;
 	}
 
-	/// Run
-	pub fn run(&self) -> bool
+	/// Operate move
+	fn operate_move(&self, _a_rule: &str, _a_bucket: &str, a_files: &[String], _a_archive: &str) -> bool
 	{
-		// Choose strategy
-		match self.task.task.rotate_strategy.as_str()
+		for i_file in a_files
 		{
-			// Delete
-			"delete" => {},
-
-			// Move
-			"move" =>
+			if !self.file_move(i_file)
 			{
-				let l_path = PathBuf::new().join(self.task.task.path.clone()).join(DIRECTORY_MOVE);
+				return false;
+			}
+		}
+		return true;
+	}
 
-				// Moved directory does not exist
-				if !l_path.exists()
-				{
-					// Create moved directory
-					match fs::create_dir(l_path.clone())
-					{
-						Ok(_) => {},
-						Err(m_error) =>
-						{
-							println!("Error: {}.{} failed to create directory '{}'!\n{}", self.task.cfg.name, self.task.name, l_path.display(), m_error.to_string());
-							return false;
+	/// Operate unknown
+	fn operate_unknown(&self, _a_rule: &str, _a_bucket: &str, _a_files: &[String], _a_archive: &str) -> bool
+	{
+		// Always return false
+		return false;
+	}
+
+	/// Pattern
+	///
+	/// Converts the file pattern into a regular expression,
+	/// capturing the stamp of a file name.
+	fn pattern(&self) -> Option<Regex>
+	{
+		// Get stamp expression
+		let l_format = &self.task.task.stamp_format;
+		let l_stamp = match Time::regex(l_format)
+		{
+			Some(m_stamp) => format!("({})", m_stamp),
+			None =>
+			{
+				eprintln!("Error: {}.{} invalid stamp format '{}'!", self.task.cfg.name, self.task.name, l_format);
+				return None;
+			},
+		};
+
+		// Convert literal parts
+		let l_parts = self.artifact.file_pattern
+			.split(MACRO_STAMP)
+			.map(Rotate::wildcard)
+			.collect::<Vec<String>>()
+		;
+
+		// Create regular expression
+		return self.regex(&format!("^{}$", l_parts.join(l_stamp.as_str())));
+	}
+
+	/// Plan
+	///
+	/// Decides for every backup, whether it is kept or rotated.
+	/// Hourly is always reduced first, then daily, weekly, monthly, yearly,
+	/// then retention by count and finally the pruning limits.
+	pub fn plan(&self) -> Option<Vec<RotateAction>>
+	{
+		// Create plan
+		let mut l_plan = Vec::<RotateAction>::new();
+
+		// Get files, newest first
+		let mut l_files = match self.list_stamped()
+		{
+			Some(m_files) => m_files,
+			None => return None,
+		};
+
+		// Reduce buckets
+		let l_rules: [(bool, RotateBucket, &str); 5] =
+		[
+			(self.artifact.rotate.hourly, Rotate::bucket_hourly, "hourly"),
+			(self.artifact.rotate.daily, Rotate::bucket_daily, "daily"),
+			(self.artifact.rotate.weekly, Rotate::bucket_weekly, "weekly"),
+			(self.artifact.rotate.monthly, Rotate::bucket_monthly, "monthly"),
+			(self.artifact.rotate.yearly, Rotate::bucket_yearly, "yearly"),
+		];
+		for (i_enabled, i_bucket, i_rule) in l_rules
+		{
+			if i_enabled
+			{
+				self.plan_bucket(&mut l_files, &mut l_plan, i_bucket, i_rule);
+			}
+		}
+
+		// Retention by count
+		if self.artifact.rotate.is_keep()
+		{
+			self.plan_keep(&mut l_files, &mut l_plan);
+		}
+
+		// Pruning limits
+		if self.artifact.rotate.is_prune() && !self.plan_prune(&mut l_files, &mut l_plan)
+		{
+			return None;
+		}
+
+		// One action per file
+		let l_plan = Rotate::plan_unique(l_plan);
+
+		// Debug
+		if self.task.cfg.debug
+		{
+			dbg!(l_plan.clone());
+		}
+
+		// Done
+		return Some(l_plan);
+	}
+
+	/// Plan bucket
+	///
+	/// Reduces every bucket, except the most recent one, to its last file.
+	fn plan_bucket(&self, a_files: &mut Vec<RotateFile>, a_plan: &mut Vec<RotateAction>, a_bucket: RotateBucket, a_rule: &str)
+	{
+		// Create tree, files oldest first
+		let l_zone = self.task.cfg.get_zone();
+		let mut l_tree = BTreeMap::<String, Vec<RotateFile>>::new();
+		for i_file in a_files.iter().rev()
+		{
+			l_tree.entry(a_bucket(&Time::zoned(&i_file.time, &l_zone))).or_default().push(i_file.clone());
+		}
+
+		// Keep most recent bucket
+		if let Some((l_bucket, l_files)) = l_tree.pop_last()
+		{
+			for i_file in l_files.iter().rev()
+			{
+				a_plan.push(Rotate::action(&l_bucket, i_file, true, a_rule));
+			}
+		}
+
+		// Iterate over tree
+		let mut l_drop = BTreeSet::<String>::new();
+		for (i_bucket, i_files) in l_tree.iter().rev()
+		{
+			// Keep last file, rotate the others
+			for (i_index, i_file) in i_files.iter().rev().enumerate()
+			{
+				let l_action = Rotate::action(i_bucket, i_file, i_index == 0, a_rule);
+				if !l_action.keep
+				{
+					l_drop.insert(i_file.name.clone());
+				}
+				a_plan.push(l_action);
+			}
+		}
+
+		// Remove rotated files
+		a_files.retain(|a_file| !l_drop.contains(&a_file.name));
+	}
+
+	/// Plan keep
+	///
+	/// Keeps the last files and the most recent file of the most recent buckets.
+	fn plan_keep(&self, a_files: &mut Vec<RotateFile>, a_plan: &mut Vec<RotateAction>)
+	{
+		// Create keep map, file to bucket and rule
+		let mut l_keep = BTreeMap::<String, (String, String)>::new();
+
+		// Keep last
+		for i_file in a_files.iter().take(self.artifact.rotate.keep_last as usize)
+		{
+			l_keep.entry(i_file.name.clone()).or_insert((String::new(), String::from("keep_last")));
+		}
+
+		// Keep newest file of the newest buckets
+		let l_rules: [(u64, RotateBucket, &str); 5] =
+		[
+			(self.artifact.rotate.keep_hourly, Rotate::bucket_hourly, "keep_hourly"),
+			(self.artifact.rotate.keep_daily, Rotate::bucket_daily, "keep_daily"),
+			(self.artifact.rotate.keep_weekly, Rotate::bucket_weekly, "keep_weekly"),
+			(self.artifact.rotate.keep_monthly, Rotate::bucket_monthly, "keep_monthly"),
+			(self.artifact.rotate.keep_yearly, Rotate::bucket_yearly, "keep_yearly"),
+		];
+		let l_zone = self.task.cfg.get_zone();
+		for (i_count, i_bucket, i_rule) in l_rules
+		{
+			let mut l_last: Option<String> = None;
+			let mut l_kept: u64 = 0;
+			for i_file in a_files.iter()
+			{
+				// Enough buckets kept
+				if l_kept >= i_count
+				{
+					break;
+				}
+
+				// First file of a new bucket
+				let l_bucket = i_bucket(&Time::zoned(&i_file.time, &l_zone));
+				if l_last.as_ref() != Some(&l_bucket)
+				{
+					l_keep.entry(i_file.name.clone()).or_insert((l_bucket.clone(), i_rule.to_string()));
+					l_last = Some(l_bucket);
+					l_kept += 1;
+				}
+			}
+		}
+
+		// Iterate over files
+		for i_file in a_files.iter()
+		{
+			match l_keep.get(&i_file.name)
+			{
+				Some((m_bucket, m_rule)) => a_plan.push(Rotate::action(m_bucket, i_file, true, m_rule)),
+				None => a_plan.push(Rotate::action("", i_file, false, "keep")),
+			}
+		}
+
+		// Remove rotated files
+		a_files.retain(|a_file| a_file.pinned || l_keep.contains_key(&a_file.name));
+	}
+
+	/// Plan prune
+	///
+	/// Rotates the oldest files exceeding the maximum age or total size.
+	fn plan_prune(&self, a_files: &mut Vec<RotateFile>, a_plan: &mut Vec<RotateAction>) -> bool
+	{
+		// Get oldest time to keep
+		let l_oldest = match self.task.limit_age("max age", &self.artifact.rotate.max_age)
+		{
+			Some(m_oldest) => m_oldest,
+			None => return false,
+		};
+
+		// Get max total size
+		let l_max_size = match self.task.limit_size("max total size", &self.artifact.rotate.max_total_size)
+		{
+			Some(m_size) => m_size,
+			None => return false,
+		};
+
+		// Iterate over files, newest first
+		let mut l_drop = BTreeSet::<String>::new();
+		let mut l_total: u64 = 0;
+		let mut l_seen = HashSet::<(u64, u64)>::new();
+		for (i_index, i_file) in a_files.iter().enumerate()
+		{
+			// Sum up size, files shared with newer snapshots are counted there
+			l_total += Rotate::size_once(&self.task.task.path.join(&i_file.name), &mut l_seen);
+
+			// Too old
+			let l_age = match l_oldest
+			{
+				Some(m_oldest) => i_file.time < m_oldest,
+				None => false,
+			};
+
+			// Too large
+			let l_size = match l_max_size
+			{
+				Some(m_max_size) => l_total > m_max_size,
+				None => false,
+			};
+
+			// Get rule, always keeping the most recent ones
+			let l_rule = match ((i_index as u64) < self.artifact.rotate.min_keep, l_age, l_size)
+			{
+				(true, _, _) => "min_keep",
+				(false, true, _) => "max_age",
+				(false, false, true) => "max_total_size",
+				(false, false, false) => "prune",
+			};
+
+			// Create action
+			let l_action = Rotate::action("", i_file, l_rule == "min_keep" || l_rule == "prune", l_rule);
+			if !l_action.keep
+			{
+				l_drop.insert(i_file.name.clone());
+			}
+			a_plan.push(l_action);
+		}
+
+		// Remove rotated files
+		a_files.retain(|a_file| !l_drop.contains(&a_file.name));
+
+		// Done
+		return true;
+	}
+
+	/// Plan unique
+	///
+	/// Reduces the plan to one action per file, since every enabled rule keeps the files it does not rotate.
+	/// A rotation wins over a pinning, which wins over the first rule keeping the file.
+	fn plan_unique(a_plan: Vec<RotateAction>) -> Vec<RotateAction>
+	{
+		let mut l_order = Vec::<String>::new();
+		let mut l_actions = BTreeMap::<String, RotateAction>::new();
+		for i_action in a_plan
+		{
+			match l_actions.get(&i_action.file)
+			{
+				Some(m_action) if (!m_action.keep, m_action.pinned) >= (!i_action.keep, i_action.pinned) => {},
+				Some(_) => { l_actions.insert(i_action.file.clone(), i_action); },
+				None =>
+				{
+					l_order.push(i_action.file.clone());
+					l_actions.insert(i_action.file.clone(), i_action);
+				},
+			}
+		}
+		return l_order.into_iter().filter_map(|a_file| l_actions.remove(&a_file)).collect();
+	}
+
+	/// Print
+	///
+	/// Prints a plan grouped by rule and bucket.
+	pub fn print(&self, a_plan: &Vec<RotateAction>)
+	{
+		// Get prefix
+		let l_prefix = match self.artifact.name.is_empty()
+		{
+			true => format!("{}.{} plan", self.task.cfg.name, self.task.name),
+			false => format!("{}.{} plan {}", self.task.cfg.name, self.task.name, self.artifact.name),
+		};
+
+		// Nothing to do
+		if a_plan.is_empty()
+		{
+			println!("{} (nothing to rotate).", l_prefix);
+			return;
+		}
+
+		// Iterate over plan
+		let mut l_group: Option<(&str, &str)> = None;
+		for i_action in a_plan
+		{
+			// New group
+			if l_group != Some((i_action.rule.as_str(), i_action.bucket.as_str()))
+			{
+				match i_action.bucket.is_empty()
+				{
+					true => println!("{} {}:", l_prefix, i_action.rule),
+					false => println!("{} {} {}:", l_prefix, i_action.rule, i_action.bucket),
+				}
+				l_group = Some((i_action.rule.as_str(), i_action.bucket.as_str()));
+			}
+
+			// Action
+			match (i_action.keep, i_action.pinned)
+			{
+				(true, true) => println!("  keep {} (pinned)", i_action.file),
+				(true, false) => println!("  keep {}", i_action.file),
+				(false, _) => println!("  {} {}", self.task.task.rotate_strategy, i_action.file),
+			}
+		}
+	}
+
+	/// Regex
+	fn regex(&self, a_expr: &str) -> Option<Regex>
+	{
+		match Regex::new(a_expr)
+		{
+			Ok(m_regex) => return Some(m_regex),
+			Err(m_error) =>
+			{
+				eprintln!("Error: {}.{} invalid regular expression '{}'!\n{}", self.task.cfg.name, self.task.name, a_expr, m_error.to_string());
+				return None;
+			}
+		}
+	}
+
+	/// Remove
+	///
+	/// Removes a file or a whole directory,
+	/// including read-only directories (e.g. copied into snapshots).
+	pub fn remove(a_path: &Path) -> io::Result<()>
+	{
+		match fs::symlink_metadata(a_path)?.is_dir()
+		{
+			true =>
+			{
+				Rotate::writable(a_path)?;
+				return fs::remove_dir_all(a_path);
+			},
+			false => return fs::remove_file(a_path),
+		}
+	}
+
+	/// Report
+	fn report(&self, a_verb: &str, a_count: usize, a_pinned: usize)
+	{
+		// Get prefix
+		let l_prefix = match self.artifact.name.is_empty()
+		{
+			true => format!("{}.{} rotate {}", self.task.cfg.name, self.task.name, a_verb),
+			false => format!("{}.{} rotate {} {}", self.task.cfg.name, self.task.name, self.artifact.name, a_verb),
+		};
+
+		// Get past tense of strategy
+		let l_done = match self.task.task.rotate_strategy.as_str()
+		{
+			"command" => String::from("handed over"),
+			m_strategy => format!("{}d", m_strategy),
+		};
+
+		// Display rotation status
+		if a_count > 0
+		{
+			println!("{} {} {} {}.", l_prefix, l_done, a_count, Rotate::word(a_count));
+		}
+
+		// Display pinned status
+		if a_pinned > 0
+		{
+			println!("{} kept {} pinned {}.", l_prefix, a_pinned, Rotate::word(a_pinned));
+		}
+	}
+
+	/// Run
+	pub fn run(&self, a_run: &str) -> bool
+	{
+		// Choose strategy
+		match self.task.task.rotate_strategy.as_str()
+		{
+			// Command or delete
+			"command" | "delete" => {},
+
+			// Archive or move
+			"archive" | "move" =>
+			{
+				let l_path = self.target.clone();
+
+				// Moved directory does not exist
+				if !l_path.exists()
+				{
+					// Create moved directory
+					match fs::create_dir_all(l_path.clone())
+					{
+						Ok(_) => {},
+						Err(m_error) =>
+						{
+							eprintln!("Error: {}.{} failed to create directory '{}'!\n{}", self.task.cfg.name, self.task.name, l_path.display(), m_error.to_string());
+							return false;
 						}
 					}
 				}
@@ -295,88 +1146,576 @@ impl Rotate
 			// Unknown
 			_ =>
 			{
-				println!("Error: {}.{} unknown rotate strategy '{}'!", self.task.cfg.name, self.task.name, self.task.task.rotate_strategy);
+				eprintln!("Error: {}.{} unknown rotate strategy '{}'!", self.task.cfg.name, self.task.name, self.task.task.rotate_strategy);
 				return false;
 			},
 		}
 
-		// Run hourly
-		if !self.run_hourly()
+		// Get plan
+		let l_plan = match self.plan()
+		{
+			Some(m_plan) => m_plan,
+			None => return false,
+		};
+
+		// Too many files to rotate
+		if !self.task.cfg.allow_mass_rotate && !self.guard(&l_plan)
 		{
 			return false;
 		}
 
-		// Run daily
-		if !self.run_daily()
+		// Group files to rotate by rule and bucket
+		let mut l_groups = Vec::<(String, String, Vec<String>)>::new();
+		for i_action in l_plan.iter().filter(|a_action| !a_action.keep)
 		{
-			return false;
+			match l_groups.last_mut()
+			{
+				Some(m_group) if m_group.0 == i_action.rule && m_group.1 == i_action.bucket => m_group.2.push(i_action.file.clone()),
+				_ => l_groups.push((i_action.rule.clone(), i_action.bucket.clone(), vec![i_action.file.clone()])),
+			}
 		}
 
-		// Run monthly
-		if !self.run_monthly()
+		// Iterate over groups
+		for (i_rule, i_bucket, i_files) in &l_groups
 		{
-			return false;
+			// Get archive name
+			let l_archive = match self.task.task.rotate_strategy.as_str()
+			{
+				"archive" => self.archive_name(i_rule, i_bucket),
+				_ => String::new(),
+			};
+
+			// Describe files for the journal, before they are gone
+			let l_entries = i_files
+				.iter()
+				.map(|a_file| self.journal(a_run, i_rule, i_bucket, a_file, &l_archive))
+				.collect::<Vec<JournalEntry>>()
+			;
+
+			// Operate on the files
+			let l_done = (self.operate)(&self, i_rule, i_bucket, i_files, &l_archive);
+
+			// Journal, after a failure only the files already gone
+			let l_entries = l_entries
+				.into_iter()
+				.filter(|a_entry| l_done || fs::symlink_metadata(self.task.task.path.join(&a_entry.file)).is_err())
+				.collect::<Vec<JournalEntry>>()
+			;
+			if !Journal::append(&self.task.task.path, &l_entries)
+			{
+				return false;
+			}
+			if !l_done
+			{
+				return false;
+			}
 		}
 
-		// Run yearly
-		if !self.run_yearly()
+		// Create counters by rule, rotated and pinned
+		let mut l_counts = Vec::<(String, usize, usize)>::new();
+
+		// Iterate over files rotated or pinned
+		for i_action in l_plan.iter().filter(|a_action| !a_action.keep || a_action.pinned)
 		{
-			return false;
+			// Count
+			let l_index = match l_counts.iter().position(|a_count| a_count.0 == i_action.rule)
+			{
+				Some(m_index) => m_index,
+				None =>
+				{
+					l_counts.push((i_action.rule.clone(), 0, 0));
+					l_counts.len() - 1
+				},
+			};
+			match i_action.pinned
+			{
+				true => l_counts[l_index].2 += 1,
+				false => l_counts[l_index].1 += 1,
+			}
+		}
+
+		// Display rotation status
+		for (i_rule, i_count, i_pinned) in l_counts
+		{
+			self.report(&i_rule, i_count, i_pinned);
+		}
+
+		// Display journal run
+		if !l_groups.is_empty()
+		{
+			println!("{}.{} rotation journaled as run '{}'.", self.task.cfg.name, self.task.name, a_run);
 		}
 
 		// Done
 		return true;
 	}
 
-	/// Run daily
-	fn run_daily(&self) -> bool
+	/// Size
+	///
+	/// Gets the size of a file or the total size of a directory.
+	pub fn size(a_path: &Path) -> u64
 	{
-		// No daily
-		if !self.task.task.rotate.daily
+		return Rotate::size_once(a_path, &mut HashSet::new());
+	}
+
+	/// Size once
+	///
+	/// Gets the size like "size", but counts hard linked files (e.g. unchanged files
+	/// of snapshots) only the first time they are seen with the same set.
+	pub fn size_once(a_path: &Path, a_seen: &mut HashSet<(u64, u64)>) -> u64
+	{
+		let l_meta = match fs::symlink_metadata(a_path)
+		{
+			Ok(m_meta) => m_meta,
+			Err(_m_error) => return 0,
+		};
+		if !l_meta.is_dir()
 		{
-			return true;
+			#[cfg(unix)]
+			{
+				use std::os::unix::fs::MetadataExt;
+				if l_meta.nlink() > 1 && !a_seen.insert((l_meta.dev(), l_meta.ino()))
+				{
+					return 0;
+				}
+			}
+			return l_meta.len();
 		}
-
-		// Perform rotation
-		return self.rotate(REGEXP_DAILY, "daily");
+		let l_items = match fs::read_dir(a_path)
+		{
+			Ok(m_items) => m_items,
+			Err(_m_error) => return 0,
+		};
+		let mut l_size: u64 = 0;
+		for i_item in l_items.filter_map(|a_item| a_item.ok())
+		{
+			l_size += Rotate::size_once(&i_item.path(), a_seen);
+		}
+		return l_size;
 	}
 
-	/// Run hourly
-	fn run_hourly(&self) -> bool
+	/// Sync dir
+	///
+	/// Syncs a directory, so renames inside it survive a crash.
+	/// Directories can not be opened for syncing on every system, where this does nothing.
+	pub fn sync_dir(a_path: &Path) -> io::Result<()>
 	{
-		// No daily
-		if !self.task.task.rotate.hourly
+		#[cfg(unix)]
+		{
+			return File::open(a_path)?.sync_all();
+		}
+		#[cfg(not(unix))]
 		{
-			return true;
+			let _ = a_path;
+			return Ok(());
 		}
+	}
+
+	/// Tarball
+	///
+	/// Writes a tarball compressed with "gzip", "xz" or "zstd" (the default) into a ".part" file,
+	/// synced before it gets its final name. The entries are appended by the given function.
+	pub fn tarball(a_archive: &Path, a_compression: &str, a_append: impl FnOnce(&mut tar::Builder<&mut dyn Write>) -> io::Result<()>) -> io::Result<()>
+	{
+		// Create tarball
+		let l_part = PathBuf::from(format!("{}.part", a_archive.display()));
+		let l_file = File::create(&l_part)?;
+		let l_file = match a_compression
+		{
+			"gzip" => Rotate::tarball_write(GzEncoder::new(l_file, Compression::default()), a_append)?.finish()?,
+			"xz" => Rotate::tarball_write(XzEncoder::new(l_file, 6), a_append)?.finish()?,
+			_ => Rotate::tarball_write(zstd::Encoder::new(l_file, 0)?, a_append)?.finish()?,
+		};
+
+		// Sync
+		l_file.sync_all()?;
+
+		// Rename
+		return fs::rename(&l_part, a_archive);
+	}
 
-		// Perform rotation
-		return self.rotate(REGEXP_HOURLY, "hourly");
+	/// Tarball write
+	///
+	/// Writes the tarball into the compressing writer, finished with its trailer.
+	fn tarball_write<W: Write>(mut a_writer: W, a_append: impl FnOnce(&mut tar::Builder<&mut dyn Write>) -> io::Result<()>) -> io::Result<W>
+	{
+		let mut l_builder = tar::Builder::new(&mut a_writer as &mut dyn Write);
+		a_append(&mut l_builder)?;
+		l_builder.finish()?;
+		drop(l_builder);
+		return Ok(a_writer);
 	}
 
-	/// Run monthly
-	fn run_monthly(&self) -> bool
+	/// To JSON
+	pub fn to_json(&self, a_plan: &Vec<RotateAction>) -> serde_json::Value
 	{
-		// No daily
-		if !self.task.task.rotate.monthly
+		return json!(
 		{
-			return true;
+			"artifact": self.artifact.name,
+			"config": self.task.cfg.name,
+			"strategy": self.task.task.rotate_strategy,
+			"task": self.task.name,
+			"actions": a_plan,
+		});
+	}
+
+	/// Transfer
+	///
+	/// Moves a file, falling back to copy, sync, verify and delete,
+	/// if source and target are on different file systems.
+	pub fn transfer(a_source: &Path, a_target: &Path) -> io::Result<()>
+	{
+		// Rename
+		match fs::rename(a_source, a_target)
+		{
+			Ok(_) => return Ok(()),
+			Err(m_error) if m_error.kind() == io::ErrorKind::CrossesDevices => {},
+			Err(m_error) => return Err(m_error),
+		}
+
+		// Copy into partial file or directory, synced
+		let l_part = PathBuf::from(format!("{}.part", a_target.display()));
+		if let Err(m_error) = Rotate::copy(a_source, &l_part)
+		{
+			let _ = Rotate::remove(&l_part);
+			return Err(m_error);
 		}
 
-		// Perform rotation
-		return self.rotate(REGEXP_MONTHLY, "monthly");
+		// Verify
+		if Journal::checksum(a_source)? != Journal::checksum(&l_part)?
+		{
+			let _ = Rotate::remove(&l_part);
+			return Err(io::Error::other("checksum of copy differs"));
+		}
+
+		// Finish copy, durable before the source is deleted
+		fs::rename(&l_part, a_target)?;
+		if let Some(m_parent) = a_target.parent()
+		{
+			Rotate::sync_dir(m_parent)?;
+		}
+		return Rotate::remove(a_source);
 	}
 
-	/// Run yearly
-	fn run_yearly(&self) -> bool
+	/// Writable
+	///
+	/// Makes a directory and all directories inside it accessible and writable for
+	/// their owner, so their entries can be removed. Symbolic links are not followed.
+	/// Elsewhere than on unix, read-only directories do not protect their entries.
+	fn writable(a_path: &Path) -> io::Result<()>
 	{
-		// No daily
-		if !self.task.task.rotate.yearly
+		#[cfg(unix)]
 		{
-			return true;
+			use std::os::unix::fs::PermissionsExt;
+			let mut l_perm = fs::symlink_metadata(a_path)?.permissions();
+			if l_perm.mode() & 0o700 != 0o700
+			{
+				l_perm.set_mode(l_perm.mode() | 0o700);
+				fs::set_permissions(a_path, l_perm)?;
+			}
+			for i_item in fs::read_dir(a_path)?
+			{
+				let l_item = i_item?;
+				if l_item.file_type()?.is_dir()
+				{
+					Rotate::writable(&l_item.path())?;
+				}
+			}
+		}
+		#[cfg(not(unix))]
+		{
+			let _ = a_path;
 		}
+		return Ok(());
+	}
+
+	/// Wildcard
+	///
+	/// Converts text with "*" and "?" wildcards into a regular expression.
+	pub fn wildcard(a_str: &str) -> String
+	{
+		return escape(a_str).replace("\\*", ".*").replace("\\?", ".");
+	}
+
+	/// Word
+	pub fn word(a_count: usize) -> &'static str
+	{
+		return match a_count
+		{
+			1 => "file",
+			_ => "files",
+		};
+	}
+}
+
+/// Tests mod
+mod tests
+{
+	/// Actions
+	///
+	/// Reduces a plan to the file, keep flag, pinned flag, bucket and rule of every action.
+	#[cfg(test)]
+	fn actions(a_plan: &[crate::vault::rotate::RotateAction]) -> Vec<(&str, bool, bool, &str, &str)>
+	{
+		return a_plan.iter().map(|a_action| (a_action.file.as_str(), a_action.keep, a_action.pinned, a_action.bucket.as_str(), a_action.rule.as_str())).collect();
+	}
+
+	/// File
+	///
+	/// Creates a file named after its stamp, e.g. "2024-01-02_03" for 03:00 UTC.
+	#[cfg(test)]
+	fn file(a_stamp: &str, a_pinned: bool) -> crate::vault::rotate::RotateFile
+	{
+		use chrono::NaiveDateTime as NaiveDateTime;
+		let l_time = NaiveDateTime::parse_from_str(&format!("{}-00-00", a_stamp), "%Y-%m-%d_%H-%M-%S").unwrap();
+		return crate::vault::rotate::RotateFile
+		{
+			name: a_stamp.to_string(),
+			pinned: a_pinned,
+			time: l_time.and_utc(),
+		};
+	}
+
+	/// Guard
+	#[test]
+	fn guard()
+	{
+		use crate::vault::config::ConfigTaskRotate as ConfigTaskRotate;
+		use crate::vault::rotate::Rotate as Rotate;
+		let l_plan = |a_rotated: usize| (0..4)
+			.map(|a_index| Rotate::action("", &file(&format!("2024-01-0{}_00", a_index + 1), false), a_index >= a_rotated, "keep"))
+			.chain([Rotate::action("2024", &file("2024-01-01_00", false), true, "yearly")])
+			.collect::<Vec<_>>()
+		;
+
+		// No limits
+		let mut l_rotate = rotate(ConfigTaskRotate::default(), &std::env::temp_dir());
+		assert!(l_rotate.guard(&l_plan(4)));
+
+		// Max rotate count
+		l_rotate.task.task.max_rotate_count = 2;
+		assert!(l_rotate.guard(&l_plan(2)));
+		assert!(!l_rotate.guard(&l_plan(3)));
+
+		// Max rotate fraction of the distinct files
+		l_rotate.task.task.max_rotate_count = 0;
+		l_rotate.task.task.max_rotate_fraction = 0.5;
+		assert!(l_rotate.guard(&l_plan(2)));
+		assert!(!l_rotate.guard(&l_plan(3)));
 
-		// Perform rotation
-		return self.rotate(REGEXP_YEARLY, "yearly");
+		// Pinned files are kept
+		let l_pinned = [Rotate::action("", &file("2024-01-01_00", true), false, "keep"), Rotate::action("", &file("2024-01-02_00", true), false, "keep")];
+		assert!(l_rotate.guard(&l_pinned));
+	}
+
+	/// Plan bucket
+	#[test]
+	fn plan_bucket()
+	{
+		use crate::vault::config::ConfigTaskRotate as ConfigTaskRotate;
+		use crate::vault::rotate::Rotate as Rotate;
+		let l_rotate = rotate(ConfigTaskRotate::default(), &std::env::temp_dir());
+
+		// Daily keeps the whole most recent day and the last file of every other day
+		let mut l_files = ["2024-01-03_10", "2024-01-03_08", "2024-01-02_12", "2024-01-02_08", "2024-01-01_23", "2024-01-01_01"]
+			.iter()
+			.map(|a_stamp| file(a_stamp, false))
+			.collect::<Vec<_>>()
+		;
+		let mut l_plan = Vec::new();
+		l_rotate.plan_bucket(&mut l_files, &mut l_plan, Rotate::bucket_daily, "daily");
+		assert_eq!(actions(&l_plan),
+		[
+			("2024-01-03_10", true, false, "2024-01-03", "daily"),
+			("2024-01-03_08", true, false, "2024-01-03", "daily"),
+			("2024-01-02_12", true, false, "2024-01-02", "daily"),
+			("2024-01-02_08", false, false, "2024-01-02", "daily"),
+			("2024-01-01_23", true, false, "2024-01-01", "daily"),
+			("2024-01-01_01", false, false, "2024-01-01", "daily"),
+		]);
+		assert_eq!(l_files.iter().map(|a_file| a_file.name.as_str()).collect::<Vec<_>>(), ["2024-01-03_10", "2024-01-03_08", "2024-01-02_12", "2024-01-01_23"]);
+
+		// Weekly uses ISO weeks, which may start in the previous year, pinned files stay
+		let mut l_files = [("2025-01-01_10", false), ("2024-12-30_10", false), ("2024-12-29_10", false), ("2024-12-28_10", true), ("2024-12-23_10", false), ("2021-01-03_10", false), ("2021-01-02_10", false)]
+			.iter()
+			.map(|(a_stamp, a_pinned)| file(a_stamp, *a_pinned))
+			.collect::<Vec<_>>()
+		;
+		let mut l_plan = Vec::new();
+		l_rotate.plan_bucket(&mut l_files, &mut l_plan, Rotate::bucket_weekly, "weekly");
+		assert_eq!(actions(&l_plan),
+		[
+			("2025-01-01_10", true, false, "2025-W01", "weekly"),
+			("2024-12-30_10", true, false, "2025-W01", "weekly"),
+			("2024-12-29_10", true, false, "2024-W52", "weekly"),
+			("2024-12-28_10", true, true, "2024-W52", "weekly"),
+			("2024-12-23_10", false, false, "2024-W52", "weekly"),
+			("2021-01-03_10", true, false, "2020-W53", "weekly"),
+			("2021-01-02_10", false, false, "2020-W53", "weekly"),
+		]);
+	}
+
+	/// Plan keep
+	#[test]
+	fn plan_keep()
+	{
+		use crate::vault::config::ConfigTaskRotate as ConfigTaskRotate;
+		let l_rotate = rotate(ConfigTaskRotate
+		{
+			keep_daily: 2,
+			keep_last: 1,
+			keep_monthly: 3,
+			keep_weekly: 2,
+			keep_yearly: 2,
+			..ConfigTaskRotate::default()
+		}, &std::env::temp_dir());
+		let mut l_files = [("2024-03-10_12", false), ("2024-03-10_08", false), ("2024-03-09_20", false), ("2024-03-08_06", false), ("2024-02-20_10", false), ("2024-01-16_10", false), ("2024-01-15_10", true), ("2024-01-14_10", false), ("2023-12-31_23", false)]
+			.iter()
+			.map(|(a_stamp, a_pinned)| file(a_stamp, *a_pinned))
+			.collect::<Vec<_>>()
+		;
+		let mut l_plan = Vec::new();
+		l_rotate.plan_keep(&mut l_files, &mut l_plan);
+
+		// The first rule keeping a file names it, e.g. the newest day, week, month and year are kept by "keep_last"
+		assert_eq!(actions(&l_plan),
+		[
+			("2024-03-10_12", true, false, "", "keep_last"),
+			("2024-03-10_08", false, false, "", "keep"),
+			("2024-03-09_20", true, false, "2024-03-09", "keep_daily"),
+			("2024-03-08_06", false, false, "", "keep"),
+			("2024-02-20_10", true, false, "2024-W08", "keep_weekly"),
+			("2024-01-16_10", true, false, "2024-01", "keep_monthly"),
+			("2024-01-15_10", true, true, "", "keep"),
+			("2024-01-14_10", false, false, "", "keep"),
+			("2023-12-31_23", true, false, "2023", "keep_yearly"),
+		]);
+		assert_eq!(l_files.len(), 6);
+	}
+
+	/// Plan prune
+	#[test]
+	fn plan_prune()
+	{
+		use chrono::Duration as Duration;
+		use crate::vault::config::ConfigTaskRotate as ConfigTaskRotate;
+		use crate::vault::rotate::RotateFile as RotateFile;
+		use crate::vault::time::Time as Time;
+		let l_dir = std::env::temp_dir().join(format!("vault-test-prune-{}", std::process::id()));
+		std::fs::create_dir_all(&l_dir).unwrap();
+
+		// Files of 10 bytes, 1, 2, 4 and 5 days old, the oldest one pinned
+		let l_files = [("a", 1, false), ("b", 2, false), ("c", 4, false), ("d", 5, true)]
+			.iter()
+			.map(|(a_name, a_days, a_pinned)|
+			{
+				std::fs::write(l_dir.join(a_name), "0123456789").unwrap();
+				RotateFile
+				{
+					name: a_name.to_string(),
+					pinned: *a_pinned,
+					time: Time::now() - Duration::days(*a_days),
+				}
+			})
+			.collect::<Vec<_>>()
+		;
+		let l_prune = |a_rotate: ConfigTaskRotate|
+		{
+			let mut l_files = l_files.clone();
+			let mut l_plan = Vec::new();
+			assert!(rotate(a_rotate, &l_dir).plan_prune(&mut l_files, &mut l_plan));
+			return l_plan.iter().map(|a_action| (a_action.file.clone(), a_action.keep, a_action.rule.clone())).collect::<Vec<_>>();
+		};
+		let l_expect = |a_list: [(&str, bool, &str); 4]| a_list.iter().map(|(a_file, a_keep, a_rule)| (a_file.to_string(), *a_keep, a_rule.to_string())).collect::<Vec<_>>();
+
+		// Max age
+		assert_eq!(l_prune(ConfigTaskRotate
+		{
+			max_age: String::from("3d"),
+			min_keep: 0,
+			..ConfigTaskRotate::default()
+		}), l_expect([("a", true, "prune"), ("b", true, "prune"), ("c", false, "max_age"), ("d", true, "max_age")]));
+
+		// Max total size, summed up newest first
+		assert_eq!(l_prune(ConfigTaskRotate
+		{
+			max_total_size: String::from("25B"),
+			min_keep: 0,
+			..ConfigTaskRotate::default()
+		}), l_expect([("a", true, "prune"), ("b", true, "prune"), ("c", false, "max_total_size"), ("d", true, "max_total_size")]));
+
+		// Newest backup kept by default, even if it alone is too large
+		assert_eq!(l_prune(ConfigTaskRotate
+		{
+			max_total_size: String::from("5B"),
+			..ConfigTaskRotate::default()
+		}), l_expect([("a", true, "min_keep"), ("b", false, "max_total_size"), ("c", false, "max_total_size"), ("d", true, "max_total_size")]));
+
+		// Min keep wins over both limits, max age over max total size
+		assert_eq!(l_prune(ConfigTaskRotate
+		{
+			max_age: String::from("1h"),
+			max_total_size: String::from("15B"),
+			min_keep: 3,
+			..ConfigTaskRotate::default()
+		}), l_expect([("a", true, "min_keep"), ("b", true, "min_keep"), ("c", true, "min_keep"), ("d", true, "max_age")]));
+
+		// Invalid limits
+		let mut l_files = l_files.clone();
+		assert!(!rotate(ConfigTaskRotate
+		{
+			max_age: String::from("3x"),
+			..ConfigTaskRotate::default()
+		}, &l_dir).plan_prune(&mut l_files, &mut Vec::new()));
+		std::fs::remove_dir_all(&l_dir).unwrap();
+	}
+
+	/// Plan unique
+	#[test]
+	fn plan_unique()
+	{
+		use crate::vault::rotate::Rotate as Rotate;
+		use crate::vault::rotate::RotateFile as RotateFile;
+		let l_file = |a_name: &str, a_pinned: bool| RotateFile
+		{
+			name: a_name.to_string(),
+			pinned: a_pinned,
+			time: crate::vault::time::Time::now(),
+		};
+		let l_plan = Rotate::plan_unique(vec!
+		[
+			Rotate::action("2024-01-02", &l_file("b", false), true, "daily"),
+			Rotate::action("2024-01-01", &l_file("a", false), true, "daily"),
+			Rotate::action("2024-01", &l_file("b", false), true, "monthly"),
+			Rotate::action("2024-01", &l_file("a", false), false, "monthly"),
+			Rotate::action("2024-01", &l_file("c", true), false, "monthly"),
+			Rotate::action("2024", &l_file("c", true), true, "yearly"),
+		]);
+		let l_plan = l_plan.iter().map(|a_action| (a_action.file.as_str(), a_action.keep, a_action.pinned, a_action.rule.as_str())).collect::<Vec<_>>();
+		assert_eq!(l_plan, vec![("b", true, false, "daily"), ("a", false, false, "monthly"), ("c", true, true, "monthly")]);
+	}
+
+	/// Rotate
+	///
+	/// Creates a rotation of a task with the given path.
+	#[cfg(test)]
+	fn rotate(a_rotate: crate::vault::config::ConfigTaskRotate, a_path: &std::path::Path) -> crate::vault::rotate::Rotate
+	{
+		use crate::vault::config::{Config, ConfigTask, ConfigTaskArtifact};
+		use crate::vault::rotate::Rotate as Rotate;
+		use crate::vault::task::Task as Task;
+		let mut l_task = Task
+		{
+			cfg: Config::default(),
+			name: String::from("test"),
+			task: ConfigTask::default(),
+		};
+		l_task.cfg.name = String::from("test");
+		l_task.task.path = a_path.to_path_buf();
+		return Rotate::new(&l_task, &ConfigTaskArtifact
+		{
+			rotate: a_rotate,
+			..ConfigTaskArtifact::default()
+		});
 	}
 }
@@ -0,0 +1,68 @@
+/// Units
+pub const UNITS: [(&str, u64); 13] =
+[
+	("b", 1),
+	("k", 1000),
+	("kb", 1000),
+	("kib", 1 << 10),
+	("m", 1000 * 1000),
+	("mb", 1000 * 1000),
+	("mib", 1 << 20),
+	("g", 1000 * 1000 * 1000),
+	("gb", 1000 * 1000 * 1000),
+	("gib", 1 << 30),
+	("tb", 1000 * 1000 * 1000 * 1000),
+	("tib", 1 << 40),
+	("", 1),
+];
+
+/// Size struct
+pub struct Size;
+
+/// Size impl
+impl Size
+{
+	/// From string
+	///
+	/// Parses a size like "500GiB", "10 MB" or "1024" into bytes.
+	pub fn from_string(a_str: &str) -> Option<u64>
+	{
+		// Split number and unit
+		let l_str = a_str.trim();
+		let l_index = l_str.find(|a_char: char| !(a_char.is_ascii_digit() || a_char == '.')).unwrap_or(l_str.len());
+		let l_number = match l_str[..l_index].parse::<f64>()
+		{
+			Ok(m_number) => m_number,
+			Err(_m_error) => return None,
+		};
+		let l_unit = l_str[l_index..].trim().to_lowercase();
+
+		// Find unit
+		for (i_unit, i_factor) in UNITS
+		{
+			if l_unit == i_unit
+			{
+				return Some((l_number * i_factor as f64) as u64);
+			}
+		}
+
+		// Unknown unit
+		return None;
+	}
+}
+
+/// Tests mod
+mod tests
+{
+	/// Smoke
+	#[test]
+	fn smoke()
+	{
+		use crate::vault::size::Size as Size;
+		assert_eq!(Size::from_string("1024"), Some(1024));
+		assert_eq!(Size::from_string("500GiB"), Some(500 << 30));
+		assert_eq!(Size::from_string("1.5 kb"), Some(1500));
+		assert_eq!(Size::from_string("10 parsecs"), None);
+		assert_eq!(Size::from_string(""), None);
+	}
+}
@@ -1,9 +1,19 @@
 // Use
 use std::fs;
+use std::io;
 use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
 use crate::vault::time::Time;
 
+/// File state
+pub const FILE_STATE: &str = "state.toml";
+
+/// File state lock, guarding the locked flag
+pub const FILE_STATE_LOCK: &str = "state.lock";
+
+/// Marker keep
+pub const MARKER_KEEP: &str = ".keep";
+
 /// State struct
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(default)]
@@ -14,6 +24,12 @@ pub struct State
 
 	/// Locked
 	pub locked: bool,
+
+	/// Pinned
+	pub pinned: Vec<String>,
+
+	/// Sequence, counting the runs of the task
+	pub seq: u64,
 }
 
 /// Default impl for State
@@ -26,6 +42,8 @@ impl Default for State
 		{
 			expires: Time::to_string(&Time::now()),
 			locked: false,
+			pinned: Vec::new(),
+			seq: 0,
 		}
 	}
 }
@@ -45,7 +63,7 @@ impl State
 				Ok(_) => {},
 				Err(m_error) =>
 				{
-					println!("Error: Failed to create path '{}'!\n{}", a_path.display(), m_error.to_string());
+					eprintln!("Error: Failed to create path '{}'!\n{}", a_path.display(), m_error.to_string());
 					return false;
 				}
 			}
@@ -66,7 +84,7 @@ impl State
 			Ok(_) => {},
 			Err(m_error) =>
 			{
-				println!("Error: Failed to create state file '{}'!\n{}", a_path.display(), m_error.to_string());
+				eprintln!("Error: Failed to create state file '{}'!\n{}", a_path.display(), m_error.to_string());
 				return false;
 			}
 		}
@@ -76,15 +94,23 @@ impl State
 	}
 
 	/// Load
+	///
+	/// A path Vault has not run on yet has no state file, which is an empty state without pins.
 	pub fn load(a_path: &PathBuf) -> Option<State>
 	{
+		// No state yet
+		if !State::path(a_path).exists()
+		{
+			return Some(State::default());
+		}
+
 		// Get path
 		let l_path = match State::path(a_path).canonicalize()
 		{
 			Ok(m_path) => m_path,
 			Err(m_error) =>
 			{
-				println!("Error: Failed to canonicalize state file '{}'!\n{}", a_path.display(), m_error.to_string());
+				eprintln!("Error: Failed to canonicalize state file '{}'!\n{}", a_path.display(), m_error.to_string());
 				return None;
 			}
 		};
@@ -95,7 +121,7 @@ impl State
 			Ok(m_data) => m_data,
 			Err(m_error) =>
 			{
-				println!("Error: Failed to read state file '{}'!\n{}", a_path.display(), m_error.to_string());
+				eprintln!("Error: Failed to read state file '{}'!\n{}", a_path.display(), m_error.to_string());
 				return None;
 			}
 		};
@@ -106,7 +132,51 @@ impl State
 			Ok(m_state) => return Some(m_state),
 			Err(m_error) =>
 			{
-				println!("Error: Failed to parse state file '{}'!\n{}", a_path.display(), m_error.to_string());
+				eprintln!("Error: Failed to parse state file '{}'!\n{}", a_path.display(), m_error.to_string());
+				return None;
+			}
+		}
+	}
+
+	/// Lock
+	///
+	/// Sets the locked flag: true if locked, false if already locked, none on errors.
+	/// Only one process at a time can create the state lock file and test and set the flag,
+	/// so two processes waiting for the same task can not both lock it.
+	pub fn lock(a_path: &PathBuf) -> Option<bool>
+	{
+		// Create state lock file, busy while another process holds it
+		let l_lock = PathBuf::new().join(a_path).join(FILE_STATE_LOCK);
+		match fs::OpenOptions::new().write(true).create_new(true).open(&l_lock)
+		{
+			Ok(_) => {},
+			Err(m_error) if m_error.kind() == io::ErrorKind::AlreadyExists => return Some(false),
+			Err(m_error) =>
+			{
+				eprintln!("Error: Failed to create state lock file '{}'!\n{}", l_lock.display(), m_error.to_string());
+				return None;
+			}
+		}
+
+		// Test and set locked flag
+		let l_locked = match State::load(a_path)
+		{
+			Some(m_state) if m_state.locked => Some(false),
+			Some(mut m_state) =>
+			{
+				m_state.locked = true;
+				State::save(a_path, &m_state).then_some(true)
+			},
+			None => None,
+		};
+
+		// Remove state lock file
+		match fs::remove_file(&l_lock)
+		{
+			Ok(_) => return l_locked,
+			Err(m_error) =>
+			{
+				eprintln!("Error: Failed to remove state lock file '{}'!\n{}", l_lock.display(), m_error.to_string());
 				return None;
 			}
 		}
@@ -115,7 +185,7 @@ impl State
 	/// Path
 	pub fn path(a_path: &PathBuf) -> PathBuf
 	{
-		return PathBuf::new().join(a_path).join("state.toml");
+		return PathBuf::new().join(a_path).join(FILE_STATE);
 	}
 
 	/// Save
@@ -127,7 +197,7 @@ impl State
 			Ok(m_path) => m_path,
 			Err(m_error) =>
 			{
-				println!("Error: Failed to canonicalize state file '{}'!\n{}", a_path.display(), m_error.to_string());
+				eprintln!("Error: Failed to canonicalize state file '{}'!\n{}", a_path.display(), m_error.to_string());
 				return false;
 			}
 		};
@@ -138,7 +208,7 @@ impl State
 			Ok(m_data) => m_data,
 			Err(m_error) =>
 			{
-				println!("Error: Failed to construct state file '{}'!\n{}", a_path.display(), m_error.to_string());
+				eprintln!("Error: Failed to construct state file '{}'!\n{}", a_path.display(), m_error.to_string());
 				return false;
 			}
 		};
@@ -149,7 +219,7 @@ impl State
 			Ok(_) => return true,
 			Err(m_error) =>
 			{
-				println!("Error: Failed to write state file '{}'!\n{}", a_path.display(), m_error.to_string());
+				eprintln!("Error: Failed to write state file '{}'!\n{}", a_path.display(), m_error.to_string());
 				return false;
 			}
 		}
@@ -0,0 +1,841 @@
+// Use
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::vec::Vec;
+use chrono::{DateTime, Utc};
+use filetime::FileTime;
+use regex::Regex;
+use zip::{AesMode, CompressionMethod, DateTime as ZipDateTime, ZipWriter};
+use zip::write::SimpleFileOptions;
+use crate::vault::config::{ConfigTaskArtifact, ConfigTaskCommandTable};
+use crate::vault::macros::{MACRO_STAMP, Macros};
+use crate::vault::rotate::Rotate;
+use crate::vault::task::Task;
+use crate::vault::time::Time;
+
+/// Step types
+pub const STEP_ARCHIVE: &str = "archive";
+pub const STEP_COMMAND: &str = "command";
+pub const STEP_SNAPSHOT: &str = "snapshot";
+pub const STEP_ZIP: &str = "zip";
+
+/// Compressions of the archive step
+pub const STEP_ARCHIVE_COMPRESSIONS: [&str; 3] = ["gzip", "xz", "zstd"];
+
+/// Symlink policies of steps
+pub const STEP_SYMLINKS: [&str; 3] = ["follow", "keep", "skip"];
+
+/// Compressions of the zip step
+pub const STEP_ZIP_COMPRESSIONS: [&str; 2] = ["deflate", "zstd"];
+
+/// StepCounts struct
+///
+/// Files and bytes of a snapshot, either copied anew or linked to the previous one.
+pub struct StepCounts
+{
+	/// Linked
+	pub linked: u64,
+
+	/// Linked bytes
+	pub linked_bytes: u64,
+
+	/// New
+	pub new: u64,
+
+	/// New bytes
+	pub new_bytes: u64,
+}
+
+/// Default impl for StepCounts
+impl Default for StepCounts
+{
+	/// Default
+	fn default() -> StepCounts
+	{
+		StepCounts
+		{
+			linked: 0,
+			linked_bytes: 0,
+			new: 0,
+			new_bytes: 0,
+		}
+	}
+}
+
+/// Step struct
+///
+/// A built-in step of a task, executed by Vault itself instead of a console command.
+pub struct Step
+{
+	/// File
+	file: String,
+
+	/// Password
+	password: String,
+
+	/// Path of the task, resolved to compare it with the entries of sources
+	path: PathBuf,
+
+	/// Run function
+	run: fn(&Step) -> bool,
+
+	/// Sources
+	sources: Vec<PathBuf>,
+
+	/// Stamp
+	stamp: String,
+
+	/// Step
+	step: ConfigTaskCommandTable,
+
+	/// Task
+	task: Task,
+}
+
+/// Step impl
+impl Step
+{
+	/// Archive
+	///
+	/// Writes the entries into a compressed tarball, synced before it gets its final name.
+	/// Following symbolic links only if asked to.
+	fn archive(&self, a_archive: &Path, a_entries: &[(PathBuf, String)]) -> io::Result<()>
+	{
+		return Rotate::tarball(a_archive, &self.step.compression, |a_builder|
+		{
+			a_builder.follow_symlinks(self.step.symlinks == "follow");
+			for (i_path, i_name) in a_entries
+			{
+				a_builder.append_path_with_name(i_path, i_name)?;
+			}
+			return Ok(());
+		});
+	}
+
+	/// Collect
+	///
+	/// Walks a source and gathers its entries with their names inside the archive.
+	/// Excluded directories are skipped as a whole, with includes only matching files are taken.
+	/// The task path inside a source is skipped, inside the task path Vault's own entries are.
+	fn collect(&self, a_path: &Path, a_name: &str, a_include: &[Regex], a_exclude: &[Regex], a_entries: &mut Vec<(PathBuf, String)>) -> io::Result<()>
+	{
+		// Excluded
+		if Step::matches(a_exclude, a_name)
+		{
+			return Ok(());
+		}
+
+		// Skipped symbolic link
+		let l_meta = self.metadata(a_path)?;
+		if l_meta.file_type().is_symlink() && self.step.symlinks == "skip"
+		{
+			return Ok(());
+		}
+
+		// Directory, sorted by name
+		if l_meta.is_dir()
+		{
+			// Task path inside a source, holding the backups themselves
+			let l_own = fs::canonicalize(a_path)? == self.path;
+			if l_own && a_name.contains('/')
+			{
+				return Ok(());
+			}
+
+			if a_include.is_empty()
+			{
+				a_entries.push((a_path.to_path_buf(), a_name.to_string()));
+			}
+			let mut l_items = fs::read_dir(a_path)?
+				.map(|a_item| a_item.map(|a_item| a_item.file_name()))
+				.collect::<io::Result<Vec<_>>>()?
+			;
+			l_items.sort();
+			for i_item in l_items
+			{
+				// Own entry of the task path
+				if l_own && Rotate::is_excluded(&i_item.to_string_lossy())
+				{
+					continue;
+				}
+
+				let l_name = format!("{}/{}", a_name, i_item.to_string_lossy());
+				self.collect(&a_path.join(&i_item), &l_name, a_include, a_exclude, a_entries)?;
+			}
+			return Ok(());
+		}
+
+		// Not included
+		if !a_include.is_empty() && !Step::matches(a_include, a_name)
+		{
+			return Ok(());
+		}
+
+		// File or symbolic link
+		a_entries.push((a_path.to_path_buf(), a_name.to_string()));
+		return Ok(());
+	}
+
+	/// Entries
+	///
+	/// Gathers the entries of all sources, each under its own name.
+	fn entries(&self) -> Option<Vec<(PathBuf, String)>>
+	{
+		// Get globs
+		let (l_include, l_exclude) = match (self.globs(&self.step.include), self.globs(&self.step.exclude))
+		{
+			(Some(m_include), Some(m_exclude)) => (m_include, m_exclude),
+			_ => return None,
+		};
+
+		// Iterate over sources
+		let mut l_entries = Vec::<(PathBuf, String)>::new();
+		for i_source in self.sources.iter()
+		{
+			// Source without name
+			let l_name = match i_source.file_name()
+			{
+				Some(m_name) => m_name.to_string_lossy().to_string(),
+				None =>
+				{
+					eprintln!("Error: {}.{} source '{}' has no name to store it under!", self.task.cfg.name, self.task.name, i_source.display());
+					return None;
+				}
+			};
+
+			// Collect entries
+			match self.collect(i_source, &l_name, &l_include, &l_exclude, &mut l_entries)
+			{
+				Ok(_) => {},
+				Err(m_error) =>
+				{
+					eprintln!("Error: {}.{} failed to read source '{}'!\n{}", self.task.cfg.name, self.task.name, i_source.display(), m_error.to_string());
+					return None;
+				}
+			}
+		}
+
+		// Done
+		return Some(l_entries);
+	}
+
+	/// Globs
+	///
+	/// Converts globs with "*" and "?" wildcards into regular expressions.
+	fn globs(&self, a_globs: &[String]) -> Option<Vec<Regex>>
+	{
+		let mut l_list = Vec::<Regex>::new();
+		for i_glob in a_globs
+		{
+			match Regex::new(&format!("^{}$", Rotate::wildcard(i_glob)))
+			{
+				Ok(m_regex) => l_list.push(m_regex),
+				Err(m_error) =>
+				{
+					eprintln!("Error: {}.{} invalid glob '{}'!\n{}", self.task.cfg.name, self.task.name, i_glob, m_error.to_string());
+					return None;
+				}
+			}
+		}
+		return Some(l_list);
+	}
+
+	/// Matches
+	///
+	/// A glob matches either the whole name inside the archive or only its last part.
+	pub fn matches(a_globs: &[Regex], a_name: &str) -> bool
+	{
+		let l_base = a_name.rsplit('/').next().unwrap_or(a_name);
+		return a_globs.iter().any(|a_glob| a_glob.is_match(a_name) || a_glob.is_match(l_base));
+	}
+
+	/// Metadata
+	///
+	/// Gets the metadata of an entry, of the link target if symbolic links are followed.
+	fn metadata(&self, a_path: &Path) -> io::Result<fs::Metadata>
+	{
+		match self.step.symlinks.as_str()
+		{
+			"follow" => return fs::metadata(a_path),
+			_ => return fs::symlink_metadata(a_path),
+		}
+	}
+
+	/// New
+	pub fn new(a_task: &Task, a_step: &ConfigTaskCommandTable, a_macros: &Macros) -> Step
+	{
+		// Get file, named after the compression by default
+		let l_file = match (a_step.file.is_empty(), a_step.kind.as_str(), a_step.compression.as_str())
+		{
+			(false, _, _) => a_step.file.clone(),
+			(true, STEP_SNAPSHOT, _) => MACRO_STAMP.to_string(),
+			(true, STEP_ZIP, _) => format!("{}.zip", MACRO_STAMP),
+			(true, _, "gzip") => format!("{}.tar.gz", MACRO_STAMP),
+			(true, _, "xz") => format!("{}.tar.xz", MACRO_STAMP),
+			(true, _, _) => format!("{}.tar.zst", MACRO_STAMP),
+		};
+
+		// Create step
+		return Step
+		{
+			file: a_macros.eval(&l_file),
+			password: a_macros.eval(&a_step.password),
+			path: fs::canonicalize(&a_task.task.path).unwrap_or_else(|_| a_task.task.path.clone()),
+			run: match a_step.kind.as_str()
+			{
+				STEP_ARCHIVE => Step::run_archive,
+				STEP_SNAPSHOT => Step::run_snapshot,
+				STEP_ZIP => Step::run_archive,
+				_ => Step::run_unknown,
+			},
+			sources: a_step.sources.iter().map(|a_source| a_task.task.path.join(a_macros.eval(a_source))).collect(),
+			stamp: a_macros.eval(MACRO_STAMP),
+			step: a_step.clone(),
+			task: a_task.clone(),
+		};
+	}
+
+	/// Run
+	pub fn run(&self) -> bool
+	{
+		return (self.run)(self);
+	}
+
+	/// Run archive
+	///
+	/// Bundles the sources into a stamped tarball or zip file inside the task path.
+	fn run_archive(&self) -> bool
+	{
+		// Hail
+		println!("{}.{} archiving into '{}'...", self.task.cfg.name, self.task.name, self.file);
+
+		// Get entries
+		let l_entries = match self.entries()
+		{
+			Some(m_entries) => m_entries,
+			None => return false,
+		};
+
+		// Create archive
+		let l_archive = self.task.task.path.join(&self.file);
+		let l_result = match self.step.kind.as_str()
+		{
+			STEP_ZIP => self.zip(&l_archive, &l_entries),
+			_ => self.archive(&l_archive, &l_entries),
+		};
+		match l_result
+		{
+			Ok(_) => {},
+			Err(m_error) =>
+			{
+				let _ = fs::remove_file(PathBuf::from(format!("{}.part", l_archive.display())));
+				eprintln!("Error: {}.{} failed to create archive '{}'!\n{}", self.task.cfg.name, self.task.name, l_archive.display(), m_error.to_string());
+				return false;
+			}
+		}
+
+		// Done
+		println!("{}.{} archived {} {} into '{}' ({} bytes).", self.task.cfg.name, self.task.name, l_entries.len(), Rotate::word(l_entries.len()), self.file, Rotate::size(&l_archive));
+		return true;
+	}
+
+	/// Run snapshot
+	///
+	/// Copies the sources into a stamped directory inside the task path,
+	/// hard linking files unchanged since the previous snapshot.
+	fn run_snapshot(&self) -> bool
+	{
+		// Hail
+		println!("{}.{} snapshotting into '{}'...", self.task.cfg.name, self.task.name, self.file);
+
+		// Get entries
+		let l_entries = match self.entries()
+		{
+			Some(m_entries) => m_entries,
+			None => return false,
+		};
+
+		// Get previous snapshot, the newest directory of the same name
+		let l_artifact = ConfigTaskArtifact
+		{
+			file_pattern: self.file.replace(&self.stamp, MACRO_STAMP),
+			name: String::new(),
+			rotate: self.task.task.rotate.clone(),
+			rotate_glob: String::from("*"),
+			rotate_source: String::from("name"),
+			verify: false,
+		};
+		let l_previous = Rotate::new(&self.task, &l_artifact)
+			.last()
+			.map(|a_file| self.task.task.path.join(a_file.name))
+			.filter(|a_path| a_path.is_dir())
+		;
+
+		// Create snapshot
+		let l_snapshot = self.task.task.path.join(&self.file);
+		let mut l_counts = StepCounts::default();
+		match self.snapshot(&l_snapshot, l_previous.as_deref(), &l_entries, &mut l_counts)
+		{
+			Ok(_) => {},
+			Err(m_error) =>
+			{
+				let _ = Rotate::remove(&PathBuf::from(format!("{}.part", l_snapshot.display())));
+				eprintln!("Error: {}.{} failed to create snapshot '{}'!\n{}", self.task.cfg.name, self.task.name, l_snapshot.display(), m_error.to_string());
+				return false;
+			}
+		}
+
+		// Done
+		println!
+		(
+			"{}.{} snapshot '{}' taken ({} new {} with {} bytes, {} linked {} with {} bytes).",
+			self.task.cfg.name,
+			self.task.name,
+			self.file,
+			l_counts.new,
+			Rotate::word(l_counts.new as usize),
+			l_counts.new_bytes,
+			l_counts.linked,
+			Rotate::word(l_counts.linked as usize),
+			l_counts.linked_bytes,
+		);
+		return true;
+	}
+
+	/// Run unknown
+	fn run_unknown(&self) -> bool
+	{
+		eprintln!("Error: {}.{} unknown step type '{}'!", self.task.cfg.name, self.task.name, self.step.kind);
+		return false;
+	}
+
+	/// Snapshot
+	///
+	/// Copies the entries into a directory, which gets its final name when complete.
+	/// Permissions and times of directories are restored last, deepest first,
+	/// so read-only directories can still be filled.
+	fn snapshot(&self, a_snapshot: &Path, a_previous: Option<&Path>, a_entries: &[(PathBuf, String)], a_counts: &mut StepCounts) -> io::Result<()>
+	{
+		// Create directory, replacing the rest of an aborted run
+		let l_part = PathBuf::from(format!("{}.part", a_snapshot.display()));
+		if fs::symlink_metadata(&l_part).is_ok()
+		{
+			Rotate::remove(&l_part)?;
+		}
+		fs::create_dir(&l_part)?;
+
+		// Iterate over entries
+		let mut l_dirs = Vec::<(PathBuf, fs::Metadata)>::new();
+		for (i_path, i_name) in a_entries
+		{
+			// Create parents, which are no entries with includes
+			let l_meta = self.metadata(i_path)?;
+			let l_target = l_part.join(i_name);
+			if let Some(m_parent) = l_target.parent()
+			{
+				fs::create_dir_all(m_parent)?;
+			}
+
+			// Symbolic link
+			if l_meta.file_type().is_symlink()
+			{
+				Rotate::copy_symlink(i_path, &l_target)?;
+				filetime::set_symlink_file_times(&l_target, FileTime::from_last_access_time(&l_meta), FileTime::from_last_modification_time(&l_meta))?;
+				continue;
+			}
+
+			// Directory
+			if l_meta.is_dir()
+			{
+				fs::create_dir_all(&l_target)?;
+				l_dirs.push((l_target, l_meta));
+				continue;
+			}
+
+			// Unchanged file, linked to the previous snapshot
+			if let Some(m_previous) = a_previous
+			{
+				let l_previous = m_previous.join(i_name);
+				if Step::unchanged(&l_meta, &l_previous) && fs::hard_link(&l_previous, &l_target).is_ok()
+				{
+					a_counts.linked += 1;
+					a_counts.linked_bytes += l_meta.len();
+					continue;
+				}
+			}
+
+			// New or changed file, copied
+			Rotate::copy_file(i_path, &l_target)?;
+			a_counts.new += 1;
+			a_counts.new_bytes += l_meta.len();
+		}
+
+		// Restore directories
+		for (i_dir, i_meta) in l_dirs.iter().rev()
+		{
+			fs::set_permissions(i_dir, i_meta.permissions())?;
+			filetime::set_file_mtime(i_dir, FileTime::from_last_modification_time(i_meta))?;
+		}
+
+		// Rename
+		return fs::rename(&l_part, a_snapshot);
+	}
+
+	/// Unchanged
+	///
+	/// A file is unchanged, if the previous snapshot has a regular file
+	/// with the same size, modification time and permissions.
+	fn unchanged(a_meta: &fs::Metadata, a_previous: &Path) -> bool
+	{
+		let l_previous = match fs::symlink_metadata(a_previous)
+		{
+			Ok(m_meta) => m_meta,
+			Err(_m_error) => return false,
+		};
+		return l_previous.is_file()
+			&& l_previous.len() == a_meta.len()
+			&& FileTime::from_last_modification_time(&l_previous) == FileTime::from_last_modification_time(a_meta)
+			&& l_previous.permissions() == a_meta.permissions()
+		;
+	}
+
+	/// Zip
+	///
+	/// Writes the entries into a zip file, synced before it gets its final name.
+	/// With a password, files are encrypted with AES-256.
+	fn zip(&self, a_archive: &Path, a_entries: &[(PathBuf, String)]) -> io::Result<()>
+	{
+		// Create zip file
+		let l_part = PathBuf::from(format!("{}.part", a_archive.display()));
+		let mut l_writer = ZipWriter::new(File::create(&l_part)?);
+		let l_method = match self.step.compression.as_str()
+		{
+			"zstd" => CompressionMethod::Zstd,
+			_ => CompressionMethod::Deflated,
+		};
+
+		// Append entries
+		let l_zone = self.task.cfg.get_zone();
+		for (i_path, i_name) in a_entries
+		{
+			// Get options from metadata
+			let l_meta = self.metadata(i_path)?;
+			let mut l_options = SimpleFileOptions::default()
+				.compression_method(l_method)
+				.large_file(l_meta.len() >= u32::MAX as u64)
+			;
+			if let Ok(m_time) = l_meta.modified()
+			{
+				let l_time = Time::zoned(&DateTime::<Utc>::from(m_time), &l_zone).naive_local();
+				if let Ok(m_time) = ZipDateTime::try_from(l_time)
+				{
+					l_options = l_options.last_modified_time(m_time);
+				}
+			}
+			#[cfg(unix)]
+			{
+				use std::os::unix::fs::PermissionsExt;
+				l_options = l_options.unix_permissions(l_meta.permissions().mode() & 0o7777);
+			}
+
+			// Symbolic link
+			if l_meta.file_type().is_symlink()
+			{
+				l_writer.add_symlink(i_name, fs::read_link(i_path)?.to_string_lossy(), l_options)?;
+				continue;
+			}
+
+			// Directory
+			if l_meta.is_dir()
+			{
+				l_writer.add_directory(format!("{}/", i_name), l_options)?;
+				continue;
+			}
+
+			// File
+			match self.password.is_empty()
+			{
+				true => l_writer.start_file(i_name, l_options)?,
+				false => l_writer.start_file(i_name, l_options.with_aes_encryption(AesMode::Aes256, &self.password))?,
+			}
+			io::copy(&mut File::open(i_path)?, &mut l_writer)?;
+		}
+
+		// Finish and sync
+		let l_file = l_writer.finish()?;
+		l_file.sync_all()?;
+
+		// Rename
+		return fs::rename(&l_part, a_archive);
+	}
+}
+
+/// Tests mod
+mod tests
+{
+	/// Archive
+	#[cfg(unix)]
+	#[test]
+	fn archive()
+	{
+		use crate::vault::config::ConfigTaskCommandTable as ConfigTaskCommandTable;
+		use crate::vault::rotate::Rotate as Rotate;
+		use tar::EntryType as EntryType;
+		for (i_symlinks, i_link) in [("follow", Some(EntryType::Regular)), ("keep", Some(EntryType::Symlink)), ("skip", None)]
+		{
+			// Excluded files and directories are left out
+			let l_dir = fixture(&format!("archive-{}", i_symlinks));
+			let l_entries = tar(&l_dir, ConfigTaskCommandTable
+			{
+				exclude: vec![String::from("*.log"), String::from("src/cache")],
+				sources: vec![l_dir.join("src").to_string_lossy().to_string()],
+				symlinks: String::from(i_symlinks),
+				..ConfigTaskCommandTable::default()
+			});
+			assert_eq!(l_entries.keys().filter(|a_name| *a_name != "src/link").collect::<Vec<&String>>(), ["src", "src/a.txt", "src/sub", "src/sub/c.txt"]);
+			assert_eq!(l_entries["src/a.txt"], (EntryType::Regular, String::from("a")));
+
+			// Symbolic link by policy
+			match i_link
+			{
+				Some(EntryType::Regular) => assert_eq!(l_entries["src/link"], (EntryType::Regular, String::from("a"))),
+				Some(m_type) => assert_eq!(l_entries["src/link"].0, m_type),
+				None => assert!(!l_entries.contains_key("src/link")),
+			}
+			Rotate::remove(&l_dir).unwrap();
+		}
+
+		// Included files only, without directories
+		let l_dir = fixture("archive-include");
+		let l_entries = tar(&l_dir, ConfigTaskCommandTable
+		{
+			include: vec![String::from("*.txt")],
+			sources: vec![l_dir.join("src").to_string_lossy().to_string()],
+			..ConfigTaskCommandTable::default()
+		});
+		assert_eq!(l_entries.keys().collect::<Vec<&String>>(), ["src/a.txt", "src/cache/d.txt", "src/sub/c.txt"]);
+
+		// Task path inside a source is left out
+		let l_name = l_dir.file_name().unwrap().to_string_lossy().to_string();
+		let l_entries = tar(&l_dir, ConfigTaskCommandTable
+		{
+			sources: vec![l_dir.to_string_lossy().to_string()],
+			..ConfigTaskCommandTable::default()
+		});
+		assert!(l_entries.contains_key(&format!("{}/src/a.txt", l_name)));
+		assert!(!l_entries.keys().any(|a_name| a_name.starts_with(&format!("{}/bk", l_name))));
+		Rotate::remove(&l_dir).unwrap();
+	}
+
+	/// Fixture
+	///
+	/// Creates a directory with the sources "src/a.txt", "src/b.log", "src/cache/d.txt",
+	/// "src/sub/c.txt" and "src/link", a symbolic link to "a.txt", next to the task path "bk".
+	#[cfg(all(test, unix))]
+	fn fixture(a_name: &str) -> std::path::PathBuf
+	{
+		use std::fs;
+		let l_dir = std::env::temp_dir().join(format!("vault-test-{}-{}", a_name, std::process::id()));
+		let _ = crate::vault::rotate::Rotate::remove(&l_dir);
+		fs::create_dir_all(l_dir.join("bk")).unwrap();
+		fs::create_dir_all(l_dir.join("src/cache")).unwrap();
+		fs::create_dir_all(l_dir.join("src/sub")).unwrap();
+		fs::write(l_dir.join("src/a.txt"), "a").unwrap();
+		fs::write(l_dir.join("src/b.log"), "b").unwrap();
+		fs::write(l_dir.join("src/cache/d.txt"), "d").unwrap();
+		fs::write(l_dir.join("src/sub/c.txt"), "c").unwrap();
+		std::os::unix::fs::symlink("a.txt", l_dir.join("src/link")).unwrap();
+		return l_dir;
+	}
+
+	/// Matches
+	#[test]
+	fn matches()
+	{
+		use regex::Regex as Regex;
+		use crate::vault::rotate::Rotate as Rotate;
+		use crate::vault::step::Step as Step;
+		let l_globs = ["*.log", "www/cache"]
+			.iter()
+			.map(|a_glob| Regex::new(&format!("^{}$", Rotate::wildcard(a_glob))).unwrap())
+			.collect::<Vec<Regex>>()
+		;
+		assert!(Step::matches(&l_globs, "www/logs/access.log"));
+		assert!(Step::matches(&l_globs, "www/cache"));
+		assert!(!Step::matches(&l_globs, "www/index.html"));
+		assert!(!Step::matches(&l_globs, "srv/www/cache"));
+	}
+
+	/// Snapshot
+	#[cfg(unix)]
+	#[test]
+	fn snapshot()
+	{
+		use std::collections::HashSet;
+		use std::fs;
+		use std::os::unix::fs::{MetadataExt, PermissionsExt};
+		use crate::vault::config::ConfigTaskCommandTable as ConfigTaskCommandTable;
+		use crate::vault::rotate::Rotate as Rotate;
+		use crate::vault::step::{STEP_SNAPSHOT, StepCounts};
+		let l_dir = fixture("snapshot");
+		let l_table = ConfigTaskCommandTable
+		{
+			file: String::from("snap-{STAMP}"),
+			kind: String::from(STEP_SNAPSHOT),
+			sources: vec![l_dir.join("src").to_string_lossy().to_string()],
+			..ConfigTaskCommandTable::default()
+		};
+		let l_ino = |a_path: &std::path::Path| fs::symlink_metadata(a_path).unwrap().ino();
+
+		// First snapshot copies every file
+		let l_first = step(&l_dir, l_table.clone());
+		let l_first_path = l_dir.join("bk").join(&l_first.file);
+		let mut l_counts = StepCounts::default();
+		l_first.snapshot(&l_first_path, None, &l_first.entries().unwrap(), &mut l_counts).unwrap();
+		assert_eq!((l_counts.new, l_counts.new_bytes, l_counts.linked, l_counts.linked_bytes), (4, 4, 0, 0));
+
+		// Second snapshot links unchanged files and copies changed ones
+		fs::write(l_dir.join("src/sub/c.txt"), "changed").unwrap();
+		fs::set_permissions(l_dir.join("src/sub"), fs::Permissions::from_mode(0o555)).unwrap();
+		let l_second = step(&l_dir, l_table.clone());
+		let l_second_path = l_dir.join("bk").join(&l_second.file);
+		let mut l_counts = StepCounts::default();
+		l_second.snapshot(&l_second_path, Some(&l_first_path), &l_second.entries().unwrap(), &mut l_counts).unwrap();
+		assert_eq!((l_counts.new, l_counts.new_bytes, l_counts.linked, l_counts.linked_bytes), (1, 7, 3, 3));
+		assert_eq!(l_ino(&l_first_path.join("src/a.txt")), l_ino(&l_second_path.join("src/a.txt")));
+		assert_ne!(l_ino(&l_first_path.join("src/sub/c.txt")), l_ino(&l_second_path.join("src/sub/c.txt")));
+		assert_eq!(fs::read_to_string(l_second_path.join("src/sub/c.txt")).unwrap(), "changed");
+		assert_eq!(fs::read_link(l_second_path.join("src/link")).unwrap(), std::path::PathBuf::from("a.txt"));
+		assert_eq!(fs::metadata(l_second_path.join("src/sub")).unwrap().permissions().mode() & 0o777, 0o555);
+
+		// Linked files count once, for the newest snapshot
+		let mut l_seen = HashSet::new();
+		assert_eq!(Rotate::size_once(&l_second_path, &mut l_seen), Rotate::size(&l_second_path));
+		assert_eq!(Rotate::size_once(&l_first_path, &mut l_seen), Rotate::size(&l_first_path.join("src/sub")) + Rotate::size(&l_first_path.join("src/link")));
+
+		// Third snapshot finds the second one as previous
+		let l_third = step(&l_dir, l_table);
+		assert!(l_third.run());
+		let l_third_path = l_dir.join("bk").join(&l_third.file);
+		assert_eq!(l_ino(&l_second_path.join("src/sub/c.txt")), l_ino(&l_third_path.join("src/sub/c.txt")));
+
+		// Read-only directories are removed
+		Rotate::remove(&l_third_path).unwrap();
+		assert!(!l_third_path.exists());
+		Rotate::remove(&l_dir).unwrap();
+	}
+
+	/// Step
+	///
+	/// Creates a step inside the task path "bk" of a fixture, stamped now.
+	#[cfg(all(test, unix))]
+	fn step(a_dir: &std::path::Path, a_table: crate::vault::config::ConfigTaskCommandTable) -> crate::vault::step::Step
+	{
+		use crate::vault::config::{Config, ConfigTask};
+		use crate::vault::macros::Macros as Macros;
+		use crate::vault::step::Step as Step;
+		use crate::vault::task::Task as Task;
+		use crate::vault::time::{FORMAT, Time, Zone};
+		let mut l_task = Task
+		{
+			cfg: Config::default(),
+			name: String::from("test"),
+			task: ConfigTask::default(),
+		};
+		l_task.cfg.name = String::from("test");
+		l_task.task.path = a_dir.join("bk");
+		return Step::new(&l_task, &a_table, &Macros::new(&Time::now(), FORMAT, &Zone::Tz(chrono_tz::UTC)));
+	}
+
+	/// Tar
+	///
+	/// Runs an archive step and reads back the type and content of its entries by name.
+	#[cfg(all(test, unix))]
+	fn tar(a_dir: &std::path::Path, a_table: crate::vault::config::ConfigTaskCommandTable) -> std::collections::BTreeMap<String, (tar::EntryType, String)>
+	{
+		use std::io::Read;
+		let l_step = step(a_dir, crate::vault::config::ConfigTaskCommandTable
+		{
+			kind: String::from(crate::vault::step::STEP_ARCHIVE),
+			..a_table
+		});
+		assert!(l_step.run());
+		let l_path = a_dir.join("bk").join(&l_step.file);
+		let mut l_archive = tar::Archive::new(zstd::Decoder::new(std::fs::File::open(&l_path).unwrap()).unwrap());
+		let mut l_entries = std::collections::BTreeMap::new();
+		for i_entry in l_archive.entries().unwrap()
+		{
+			let mut l_entry = i_entry.unwrap();
+			let l_name = l_entry.path().unwrap().to_string_lossy().trim_end_matches('/').to_string();
+			let mut l_data = String::new();
+			l_entry.read_to_string(&mut l_data).unwrap();
+			l_entries.insert(l_name, (l_entry.header().entry_type(), l_data));
+		}
+		std::fs::remove_file(&l_path).unwrap();
+		return l_entries;
+	}
+
+	/// Zip
+	#[cfg(unix)]
+	#[test]
+	fn zip()
+	{
+		use std::fs::File;
+		use std::io::Read;
+		use crate::vault::config::ConfigTaskCommandTable as ConfigTaskCommandTable;
+		use crate::vault::rotate::Rotate as Rotate;
+		use crate::vault::step::STEP_ZIP as STEP_ZIP;
+		for i_symlinks in ["follow", "keep", "skip"]
+		{
+			// Excluded files and directories are left out
+			let l_dir = fixture(&format!("zip-{}", i_symlinks));
+			let l_step = step(&l_dir, ConfigTaskCommandTable
+			{
+				exclude: vec![String::from("*.log"), String::from("src/cache")],
+				kind: String::from(STEP_ZIP),
+				sources: vec![l_dir.join("src").to_string_lossy().to_string()],
+				symlinks: String::from(i_symlinks),
+				..ConfigTaskCommandTable::default()
+			});
+			assert!(l_step.run());
+			let mut l_zip = zip::ZipArchive::new(File::open(l_dir.join("bk").join(&l_step.file)).unwrap()).unwrap();
+			let mut l_names = l_zip.file_names().filter(|a_name| *a_name != "src/link").collect::<Vec<&str>>();
+			l_names.sort();
+			assert_eq!(l_names, ["src/", "src/a.txt", "src/sub/", "src/sub/c.txt"]);
+
+			// Symbolic link by policy
+			match i_symlinks
+			{
+				"follow" =>
+				{
+					let mut l_data = String::new();
+					l_zip.by_name("src/link").unwrap().read_to_string(&mut l_data).unwrap();
+					assert_eq!(l_data, "a");
+				},
+				"keep" => assert!(l_zip.by_name("src/link").unwrap().is_symlink()),
+				_ => assert!(l_zip.by_name("src/link").is_err()),
+			}
+			Rotate::remove(&l_dir).unwrap();
+		}
+
+		// Encrypted files need the password
+		let l_dir = fixture("zip-password");
+		let l_step = step(&l_dir, ConfigTaskCommandTable
+		{
+			kind: String::from(STEP_ZIP),
+			password: String::from("secret"),
+			sources: vec![l_dir.join("src/a.txt").to_string_lossy().to_string()],
+			..ConfigTaskCommandTable::default()
+		});
+		assert!(l_step.run());
+		let mut l_zip = zip::ZipArchive::new(File::open(l_dir.join("bk").join(&l_step.file)).unwrap()).unwrap();
+		assert!(l_zip.by_name("a.txt").is_err());
+		assert!(l_zip.by_name_decrypt("a.txt", b"wrong").is_err());
+		let mut l_data = String::new();
+		l_zip.by_name_decrypt("a.txt", b"secret").unwrap().read_to_string(&mut l_data).unwrap();
+		assert_eq!(l_data, "a");
+		Rotate::remove(&l_dir).unwrap();
+	}
+}
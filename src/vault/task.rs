@@ -1,18 +1,24 @@
 // Use
 use std::cmp::Ordering;
 use std::env;
-use std::process::Command;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Instant;
 use std::vec::Vec;
-use chrono::{DateTime, Duration, Utc};
-use crate::vault::config::{Config, ConfigTask};
+use chrono::{DateTime, Duration, SubsecRound, Utc};
+use crate::vault::config::{Config, ConfigTask, ConfigTaskArtifact};
 use crate::vault::rotate::Rotate;
-use crate::vault::state::State;
+use crate::vault::state::{MARKER_KEEP, State};
+use crate::vault::step::Step;
+use crate::vault::macros::{MACRO_STAMP, Macros};
+use crate::vault::size::Size;
 use crate::vault::time::Time;
+use crate::vault::trash::Trash;
 
-/// Macros
-pub const MACRO_NOW: &str = "{NOW}";
-pub const MACRO_PATH: &str = "{PATH}";
-pub const MACRO_STAMP: &str = "{STAMP}";
+/// Lock poll interval in milliseconds
+pub const LOCK_POLL: u64 = 1000;
 
 /// Task struct
 #[derive(Clone)]
@@ -32,7 +38,7 @@ pub struct Task
 impl Task
 {
 	/// Command
-	fn command(&self) -> bool
+	fn command(&self, a_now: &DateTime<Utc>) -> bool
 	{
 		// Hail
 		println!("{}.{} executing...", self.cfg.name, self.name);
@@ -51,66 +57,66 @@ impl Task
 			Ok(_) => {},
 			Err(m_error) =>
 			{
-				println!("Error: {}.{} failed to change directory into '{}'!\n{}", self.task.config, self.name, l_path_s, m_error.to_string());
+				eprintln!("Error: {}.{} failed to change directory into '{}'!\n{}", self.task.config, self.name, l_path_s, m_error.to_string());
 				return false;
 			}
 		}
 
-		// Now
-		let l_now = Time::now();
+		// Count run
+		let mut l_state = match State::load(&self.task.path)
+		{
+			Some(m_state) => m_state,
+			None => return false,
+		};
+		l_state.seq += 1;
+		if !State::save(&self.task.path, &l_state)
+		{
+			return false;
+		}
+
+		// Get macros, with the newest backups before this run
+		let mut l_macros = self.macros(a_now);
+		self.macros_last(&mut l_macros);
 
 		// Iterate over commands
 		for i_cmd in self.task.commands.iter()
 		{
-			// No command
-			if i_cmd.is_empty()
+			// Run built-in step
+			if let Some(m_step) = i_cmd.step()
 			{
+				if !Step::new(self, m_step, &l_macros).run()
+				{
+					return false;
+				}
 				continue;
 			}
 
-			// Eval command
-			let l_str = Task::eval(i_cmd.as_str(), l_path_s, &l_now);
-
-			// Split command
-			let l_split = l_str.split(" ").collect::<Vec<&str>>();
-
-			// No split
-			if l_split.is_empty()
+			// No command
+			if i_cmd.command().is_empty()
 			{
 				continue;
 			}
 
-			// Create command
-			let mut l_cmd = Command::new(l_split[0]);
-
-			// Set working directory
-			l_cmd.current_dir(l_path.clone());
-
-			// Add arguments
-			for i_index in 1..l_split.len()
-			{
-				let l_str = l_split[i_index]
-					;
-				l_cmd.arg(l_str);
-			}
+			// Eval command
+			let l_args = l_macros.args(i_cmd.command());
 
 			// Execute command
-			let l_status = match l_cmd.status()
+			let mut l_output = String::new();
+			let l_capture = match i_cmd.capture().is_empty()
 			{
-				Ok(m_status) => m_status,
-				Err(m_error) =>
-				{
-					println!("Error: {}.{} failed to execute command '{}'!\n{}", self.task.config, self.name, l_split[0], m_error.to_string());
-					return false;
-				}
+				true => None,
+				false => Some(&mut l_output),
 			};
-
-			// Execution failed
-			if !l_status.success()
+			if !self.execute(&l_args, &l_path, l_capture)
 			{
-				println!("Error: {}.{} failed to execute command '{}'!", self.task.config, self.name, l_split[0]);
 				return false;
 			}
+
+			// Capture output for later commands
+			if !i_cmd.capture().is_empty()
+			{
+				l_macros.set_var(i_cmd.capture(), &l_output);
+			}
 		}
 
 		// Done
@@ -118,14 +124,59 @@ impl Task
 		return true;
 	}
 
-	/// Eval
-	pub fn eval(a_cmd: &str, a_path: &str, a_stamp: &DateTime<Utc>) -> String
+	/// Execute
+	///
+	/// Runs a single command, given as its arguments, inside the given directory.
+	/// With a capture, its trimmed standard output is stored there instead of being shown.
+	pub fn execute(&self, a_args: &[String], a_path: &Path, a_capture: Option<&mut String>) -> bool
 	{
-		return a_cmd
-			.replace(MACRO_NOW, Time::to_string(&Time::now()).as_str())
-			.replace(MACRO_PATH, a_path)
-			.replace(MACRO_STAMP, Time::to_string(a_stamp).as_str())
-		;
+		// No command
+		if a_args.is_empty()
+		{
+			return true;
+		}
+
+		// Create command
+		let mut l_cmd = Command::new(&a_args[0]);
+
+		// Set working directory
+		l_cmd.current_dir(a_path);
+
+		// Add arguments
+		l_cmd.args(&a_args[1..]);
+
+		// Capture standard output
+		if a_capture.is_some()
+		{
+			l_cmd.stdout(Stdio::piped());
+		}
+
+		// Execute command
+		let l_output = match l_cmd.spawn().and_then(|a_child| a_child.wait_with_output())
+		{
+			Ok(m_output) => m_output,
+			Err(m_error) =>
+			{
+				eprintln!("Error: {}.{} failed to execute command '{}'!\n{}", self.task.config, self.name, a_args[0], m_error.to_string());
+				return false;
+			}
+		};
+
+		// Execution failed
+		if !l_output.status.success()
+		{
+			eprintln!("Error: {}.{} failed to execute command '{}'!", self.task.config, self.name, a_args[0]);
+			return false;
+		}
+
+		// Store captured output
+		if let Some(m_capture) = a_capture
+		{
+			*m_capture = String::from_utf8_lossy(&l_output.stdout).trim().to_string();
+		}
+
+		// Done
+		return true;
 	}
 
 	/// Finalize
@@ -161,12 +212,57 @@ impl Task
 		return true;
 	}
 
-	/// Prepare
-	fn prepare(&mut self) -> bool
+	/// Limit age
+	///
+	/// Gets the oldest time to keep for a maximum age, none if empty or older than any time.
+	/// Returns none on an invalid maximum age.
+	pub fn limit_age(&self, a_label: &str, a_value: &str) -> Option<Option<DateTime<Utc>>>
 	{
-		// Hail
-		println!("{}.{} preparing...", self.cfg.name, self.name);
+		// No limit
+		if a_value.is_empty()
+		{
+			return Some(None);
+		}
+
+		// Get oldest time
+		match Time::duration(a_value)
+		{
+			Some(m_age) => return Some(Time::now().checked_sub_signed(m_age)),
+			None =>
+			{
+				eprintln!("Error: {}.{} invalid {} '{}'!", self.cfg.name, self.name, a_label, a_value);
+				return None;
+			},
+		}
+	}
+
+	/// Limit size
+	///
+	/// Gets a maximum size, none if empty.
+	/// Returns none on an invalid maximum size.
+	pub fn limit_size(&self, a_label: &str, a_value: &str) -> Option<Option<u64>>
+	{
+		// No limit
+		if a_value.is_empty()
+		{
+			return Some(None);
+		}
+
+		// Get size
+		match Size::from_string(a_value)
+		{
+			Some(m_size) => return Some(Some(m_size)),
+			None =>
+			{
+				eprintln!("Error: {}.{} invalid {} '{}'!", self.cfg.name, self.name, a_label, a_value);
+				return None;
+			},
+		}
+	}
 
+	/// Load
+	fn load(&mut self) -> bool
+	{
 		// Get task
 		self.task = match self.cfg.get_task(&self.name)
 		{
@@ -180,18 +276,178 @@ impl Task
 			return false;
 		}
 
+		// Done
+		return true;
+	}
+
+	/// Lock
+	///
+	/// Locks a singleton task: true if locked, false if skipped, none on errors.
+	fn lock(&self) -> Option<bool>
+	{
+		// Not singleton
+		if !self.task.singleton
+		{
+			return Some(true);
+		}
+
+		// Lock
+		if State::lock(&self.task.path)?
+		{
+			return Some(true);
+		}
+
+		// Get lock policy, the wait argument takes precedence
+		let (l_policy, l_timeout) = match self.cfg.wait
+		{
+			Some(m_timeout) => ("wait", m_timeout),
+			None => (self.task.on_locked.as_str(), self.task.on_locked_timeout),
+		};
+
+		// Choose lock policy
+		match l_policy
+		{
+			// Fail
+			"fail" =>
+			{
+				eprintln!("Error: {}.{} is locked!", self.cfg.name, self.name);
+				return None;
+			},
+
+			// Wait
+			"wait" => return self.wait(l_timeout),
+
+			// Skip
+			_ =>
+			{
+				println!("{}.{} skipped (locked).", self.cfg.name, self.name);
+				return Some(false);
+			},
+		}
+	}
+
+	/// Macros
+	///
+	/// Creates the macros of this task for a stamp.
+	pub fn macros(&self, a_stamp: &DateTime<Utc>) -> Macros
+	{
+		let l_seq = match State::load(&self.task.path)
+		{
+			Some(m_state) => m_state.seq,
+			None => 0,
+		};
+		let mut l_macros = Macros::new(a_stamp, &self.stamp_format(), &self.cfg.get_zone());
+		l_macros
+			.set("CONFIG", &self.cfg.name)
+			.set("PATH", &self.task.path.to_string_lossy())
+			.set("SEQ", &l_seq.to_string())
+			.set("TASK", &self.name)
+			.set_vars(&self.task.vars)
+		;
+		return l_macros;
+	}
+
+	/// Macros last
+	///
+	/// Sets "{LAST}" and "{PREV}" to the name and stamp of the newest backup
+	/// of the first artifact, and "{LAST:NAME}" and "{PREV:NAME}" for named artifacts.
+	/// Both are empty while there is no backup yet.
+	fn macros_last(&self, a_macros: &mut Macros)
+	{
+		let l_format = self.stamp_format();
+		let l_zone = self.cfg.get_zone();
+		for (i_index, i_artifact) in self.task.get_artifacts().iter().enumerate()
+		{
+			// Get newest backup
+			let (l_last, l_prev) = match Rotate::new(self, i_artifact).last()
+			{
+				Some(m_file) => (m_file.name, Time::format(&m_file.time, &l_format, &l_zone)),
+				None => (String::new(), String::new()),
+			};
+
+			// First artifact
+			if i_index == 0
+			{
+				a_macros.set("LAST", &l_last).set("PREV", &l_prev);
+			}
+
+			// Named artifact
+			if !i_artifact.name.is_empty()
+			{
+				a_macros.set(&format!("LAST:{}", i_artifact.name), &l_last).set(&format!("PREV:{}", i_artifact.name), &l_prev);
+			}
+		}
+	}
+
+	/// Plan
+	fn plan(&self, a_json: &mut Vec<serde_json::Value>, a_as_json: bool) -> bool
+	{
+		// Get artifacts with rotation
+		let l_artifacts = self.task.get_artifacts()
+			.into_iter()
+			.filter(|a_artifact| a_artifact.rotate.is_valid())
+			.collect::<Vec<ConfigTaskArtifact>>()
+		;
+
+		// No rotate
+		if l_artifacts.is_empty() && !a_as_json
+		{
+			println!("{}.{} plan (no rotation).", self.cfg.name, self.name);
+		}
+
+		// Iterate over artifacts
+		for i_artifact in l_artifacts
+		{
+			// Create rotate
+			let l_rotate = Rotate::new(self, &i_artifact);
+
+			// Get plan
+			let l_plan = match l_rotate.plan()
+			{
+				Some(m_plan) => m_plan,
+				None => return false,
+			};
+
+			// Output plan
+			match a_as_json
+			{
+				true => a_json.push(l_rotate.to_json(&l_plan)),
+				false => l_rotate.print(&l_plan),
+			}
+		}
+
+		// Done
+		return true;
+	}
+
+	/// Prepare
+	///
+	/// Gets a task ready to run: true if it runs, false if it is skipped, none on errors.
+	fn prepare(&mut self) -> Option<bool>
+	{
+		// Hail
+		println!("{}.{} preparing...", self.cfg.name, self.name);
+
+		// Task not enabled
+		if !self.cfg.get_task(&self.name)?.is_enabled()
+		{
+			return Some(false);
+		}
+
+		// Load task
+		if !self.load()
+		{
+			return None;
+		}
+
 		// Create state if not exist
 		if !State::create(&self.task.path)
 		{
-			return false;
+			return None;
 		}
 
 		// Load state
-		let l_state = match State::load(&self.task.path)
-		{
-			Some(m_state) => m_state,
-			None => return false,
-		};
+		let l_state = State::load(&self.task.path)?;
 
 		// Debug
 		if self.cfg.debug
@@ -206,7 +462,7 @@ impl Task
 			None =>
 			{
 				println!("{}.{} skipped (invalid: {}).", self.cfg.name, self.name, l_state.expires);
-				return false;
+				return Some(false);
 			},
 		};
 
@@ -214,40 +470,25 @@ impl Task
 		if Time::now() < l_expires
 		{
 			println!("{}.{} skipped (expires: {}).", self.cfg.name, self.name, l_state.expires);
-			return false;
+			return Some(false);
 		}
 
-		// Singleton
-		if self.task.singleton
-		{
-			// Already locked
-			if l_state.locked
-			{
-				println!("{}.{} skipped (locked).", self.cfg.name, self.name);
-				return false;
-			}
-
-			// Lock
-			else
-			{
-				let mut l_state = l_state.clone();
-				l_state.locked = true;
-				if !State::save(&self.task.path, &l_state)
-				{
-					return false;
-				}
-			}
-		}
-
-		// Done
-		return true;
+		// Lock
+		return self.lock();
 	}
 
 	/// Rotate
 	fn rotate(&self) -> bool
 	{
+		// Get artifacts with rotation
+		let l_artifacts = self.task.get_artifacts()
+			.into_iter()
+			.filter(|a_artifact| a_artifact.rotate.is_valid())
+			.collect::<Vec<ConfigTaskArtifact>>()
+		;
+
 		// No rotate
-		if !self.task.rotate.is_valid()
+		if l_artifacts.is_empty()
 		{
 			return true;
 		}
@@ -255,11 +496,24 @@ impl Task
 		// Hail
 		println!("{}.{} rotating...", self.cfg.name, self.name);
 
-		// Create rotate
-		let l_rotate = Rotate::new(self);
+		// Journal run
+		let l_run = Time::to_string(&Time::now());
+
+		// Iterate over artifacts
+		for i_artifact in l_artifacts
+		{
+			// Create rotate
+			let l_rotate = Rotate::new(self, &i_artifact);
+
+			// Run rotate
+			if !l_rotate.run(&l_run)
+			{
+				return false;
+			}
+		}
 
-		// Run rotate
-		return l_rotate.run();
+		// Expire moved files
+		return Trash::new(self).expire();
 	}
 
 	/// Run
@@ -276,7 +530,7 @@ impl Task
 		// Empty task
 		if l_task.name.is_empty()
 		{
-			println!("Error: Task name for configuration '{}' is empty!", a_cfg.name);
+			eprintln!("Error: Task name for configuration '{}' is empty!", a_cfg.name);
 			return false;
 		}
 
@@ -325,25 +579,243 @@ impl Task
 		return l_status;
 	}
 
+	/// Run pin
+	///
+	/// Pins or unpins a backup of a task.
+	pub fn run_pin(a_cfg: &Config, a_task: &str, a_file: &str, a_pin: bool, a_marker: bool) -> bool
+	{
+		// Create task
+		let mut l_task = Task
+		{
+			cfg: a_cfg.clone(),
+			name: a_task.to_string(),
+			task: ConfigTask::default(),
+		};
+
+		// Load task
+		if !l_task.load()
+		{
+			return false;
+		}
+
+		// Get file path
+		let l_path = PathBuf::new().join(l_task.task.path.clone()).join(a_file);
+		let l_marker = PathBuf::new().join(l_task.task.path.clone()).join(a_file.to_string() + MARKER_KEEP);
+
+		// File does not exist
+		if a_pin && !l_path.exists()
+		{
+			eprintln!("Error: {}.{} file '{}' does not exist!", l_task.cfg.name, l_task.name, l_path.display());
+			return false;
+		}
+
+		// Pin by marker
+		if a_pin && a_marker
+		{
+			match fs::write(&l_marker, "")
+			{
+				Ok(_) =>
+				{
+					println!("{}.{} pinned '{}' (marker).", l_task.cfg.name, l_task.name, a_file);
+					return true;
+				},
+				Err(m_error) =>
+				{
+					eprintln!("Error: {}.{} failed to create marker '{}'!\n{}", l_task.cfg.name, l_task.name, l_marker.display(), m_error.to_string());
+					return false;
+				},
+			}
+		}
+
+		// Unpin by marker
+		if !a_pin && l_marker.exists()
+		{
+			match fs::remove_file(&l_marker)
+			{
+				Ok(_) => {},
+				Err(m_error) =>
+				{
+					eprintln!("Error: {}.{} failed to remove marker '{}'!\n{}", l_task.cfg.name, l_task.name, l_marker.display(), m_error.to_string());
+					return false;
+				},
+			}
+		}
+
+		// Create state if not exist
+		if !State::create(&l_task.task.path)
+		{
+			return false;
+		}
+
+		// Load state
+		let mut l_state = match State::load(&l_task.task.path)
+		{
+			Some(m_state) => m_state,
+			None => return false,
+		};
+
+		// Update pinned files
+		l_state.pinned.retain(|a_pinned| a_pinned != a_file);
+		if a_pin
+		{
+			l_state.pinned.push(a_file.to_string());
+			l_state.pinned.sort();
+		}
+
+		// Save state
+		if !State::save(&l_task.task.path, &l_state)
+		{
+			return false;
+		}
+
+		// Done
+		match a_pin
+		{
+			true => println!("{}.{} pinned '{}'.", l_task.cfg.name, l_task.name, a_file),
+			false => println!("{}.{} unpinned '{}'.", l_task.cfg.name, l_task.name, a_file),
+		}
+		return true;
+	}
+
+	/// Run rotate
+	///
+	/// Rotates without executing the commands, or only shows the rotation plan.
+	pub fn run_rotate(a_cfg: &Config, a_task: &str, a_plan: bool, a_json: bool) -> bool
+	{
+		// Empty task
+		if a_task.is_empty()
+		{
+			eprintln!("Error: Task name for configuration '{}' is empty!", a_cfg.name);
+			return false;
+		}
+
+		// Get task names, all enabled tasks sorted by order or one specific task
+		let l_names = match a_task
+		{
+			"*" =>
+			{
+				let mut l_tasks: Vec<ConfigTask> = a_cfg.tasks.values().filter(|a_task| a_task.enabled).cloned().collect();
+				l_tasks.sort_by(Task::sort_by_order);
+				l_tasks.into_iter().map(|a_task| a_task.task).collect::<Vec<String>>()
+			},
+			_ => vec![a_task.to_string()],
+		};
+
+		// Status bool
+		let mut l_status = true;
+
+		// JSON plans
+		let mut l_json = Vec::<serde_json::Value>::new();
+
+		// Iterate over tasks
+		for i_name in l_names
+		{
+			// Create task
+			let mut l_task = Task
+			{
+				cfg: a_cfg.clone(),
+				name: i_name,
+				task: ConfigTask::default(),
+			};
+
+			// Load task
+			if !l_task.load()
+			{
+				l_status = false;
+				continue;
+			}
+
+			// Plan
+			if a_plan
+			{
+				if !l_task.plan(&mut l_json, a_json)
+				{
+					l_status = false;
+				}
+				continue;
+			}
+
+			// Create state if not exist
+			if !State::create(&l_task.task.path)
+			{
+				l_status = false;
+				continue;
+			}
+
+			// Lock
+			match l_task.lock()
+			{
+				Some(true) => {},
+				Some(false) => continue,
+				None =>
+				{
+					l_status = false;
+					continue;
+				},
+			}
+
+			// Rotate
+			if !l_task.rotate()
+			{
+				l_status = false;
+			}
+
+			// Unlock
+			if !l_task.unlock()
+			{
+				l_status = false;
+			}
+		}
+
+		// Output JSON plans
+		if a_json
+		{
+			match serde_json::to_string_pretty(&l_json)
+			{
+				Ok(m_json) => println!("{}", m_json),
+				Err(m_error) =>
+				{
+					eprintln!("Error: Failed to serialize rotation plan!\n{}", m_error.to_string());
+					return false;
+				},
+			}
+		}
+
+		// Done
+		return l_status;
+	}
+
 	/// Run one
 	fn run_one(&mut self) -> bool
 	{
-		// Prepare
-		if !self.prepare()
+		// Prepare, a skipped task has not failed
+		match self.prepare()
 		{
-			return false;
+			Some(true) => {},
+			Some(false) => return true,
+			None => return false,
 		}
 
 		// Command
-		if !self.command()
+		let l_now = Time::now();
+		if !self.command(&l_now)
 		{
-			return self.finalize();
+			self.finalize();
+			return false;
+		}
+
+		// Verify artifacts
+		if !self.verify(&l_now)
+		{
+			self.finalize();
+			return false;
 		}
 
 		// Rotate
 		if !self.rotate()
 		{
-			return self.finalize();
+			self.finalize();
+			return false;
 		}
 
 		// Finalize
@@ -356,6 +828,57 @@ impl Task
 		return true;
 	}
 
+	/// Run trash
+	///
+	/// Empties the moved directory of a task, or restores one file from it.
+	pub fn run_trash(a_cfg: &Config, a_task: &str, a_file: Option<&str>) -> bool
+	{
+		// Create task
+		let mut l_task = Task
+		{
+			cfg: a_cfg.clone(),
+			name: a_task.to_string(),
+			task: ConfigTask::default(),
+		};
+
+		// Load task
+		if !l_task.load()
+		{
+			return false;
+		}
+
+		// Create trash
+		let l_trash = Trash::new(&l_task);
+
+		// Restore or empty
+		match a_file
+		{
+			Some(m_file) => return l_trash.restore(m_file),
+			None => return l_trash.empty(),
+		}
+	}
+
+	/// Run undo
+	pub fn run_undo(a_cfg: &Config, a_task: &str, a_run: &str) -> bool
+	{
+		// Create task
+		let mut l_task = Task
+		{
+			cfg: a_cfg.clone(),
+			name: a_task.to_string(),
+			task: ConfigTask::default(),
+		};
+
+		// Load task
+		if !l_task.load()
+		{
+			return false;
+		}
+
+		// Undo
+		return Trash::new(&l_task).undo(a_run);
+	}
+
 	/// Sort by order
 	fn sort_by_order(a_left: &ConfigTask, a_right: &ConfigTask) -> Ordering
 	{
@@ -373,4 +896,174 @@ impl Task
 		}
 	}
 
+	/// Stamp format
+	///
+	/// Gets the format of new stamps.
+	/// Outside of UTC, they carry their offset to stay unambiguous across clock changes.
+	/// Existing stamps are parsed with the configured format, their offset is optional.
+	pub fn stamp_format(&self) -> String
+	{
+		let l_format = self.task.stamp_format.clone();
+		if self.cfg.is_utc() || l_format.contains("%z") || l_format.contains("%:z")
+		{
+			return l_format;
+		}
+		return l_format + "%z";
+	}
+
+	/// Target
+	///
+	/// Gets the directory the "move" strategy moves files into.
+	pub fn target(&self) -> PathBuf
+	{
+		let l_target = self.macros(&Time::now()).eval(&self.task.rotate_move_target);
+		return PathBuf::new().join(self.task.path.clone()).join(l_target);
+	}
+
+	/// Unlock
+	fn unlock(&self) -> bool
+	{
+		// Not singleton
+		if !self.task.singleton
+		{
+			return true;
+		}
+
+		// Load state
+		let mut l_state = match State::load(&self.task.path)
+		{
+			Some(m_state) => m_state,
+			None => return false,
+		};
+
+		// Unlock
+		l_state.locked = false;
+		return State::save(&self.task.path, &l_state);
+	}
+
+	/// Verify
+	///
+	/// Checks that every verified artifact was written by this run, is not smaller
+	/// than "verify_min_size" and does not deviate from the previous one by more
+	/// than "verify_max_deviation".
+	fn verify(&self, a_now: &DateTime<Utc>) -> bool
+	{
+		// Get verified artifacts
+		let l_artifacts = self.task.get_artifacts()
+			.into_iter()
+			.filter(|a_artifact| a_artifact.verify)
+			.collect::<Vec<ConfigTaskArtifact>>()
+		;
+
+		// Nothing to verify
+		if l_artifacts.is_empty()
+		{
+			return true;
+		}
+
+		// Get limits
+		let l_min_size = Size::from_string(&self.task.verify_min_size).unwrap_or(0);
+		let l_format = self.stamp_format();
+		let l_zone = self.cfg.get_zone();
+		let l_stamp = Time::format(a_now, &l_format, &l_zone);
+
+		// Get start of the run in whole seconds, as file systems store coarser times
+		let l_start = a_now.trunc_subsecs(0);
+
+		// Iterate over artifacts
+		for i_artifact in l_artifacts
+		{
+			// Get backups
+			let l_files = match Rotate::new(self, &i_artifact).list_stamped()
+			{
+				Some(m_files) => m_files,
+				None => return false,
+			};
+
+			// Find backup of this run
+			let l_index = l_files.iter().position(|a_file| match i_artifact.rotate_source.as_str()
+			{
+				"name" => Time::format(&a_file.time, &l_format, &l_zone) == l_stamp,
+				_ => a_file.time >= l_start,
+			});
+			let l_index = match l_index
+			{
+				Some(m_index) => m_index,
+				None =>
+				{
+					eprintln!("Error: {}.{} artifact '{}' missing!", self.cfg.name, self.name, i_artifact.file_pattern.replace(MACRO_STAMP, &l_stamp));
+					return false;
+				}
+			};
+			let l_file = &l_files[l_index];
+
+			// Too small
+			let l_size = Rotate::size(&self.task.path.join(&l_file.name));
+			if l_size < l_min_size
+			{
+				eprintln!("Error: {}.{} artifact '{}' too small ({} bytes, at least {} expected)!", self.cfg.name, self.name, l_file.name, l_size, l_min_size);
+				return false;
+			}
+
+			// No previous backup to compare with
+			let l_prev = match l_files.get(l_index + 1)
+			{
+				Some(m_file) => m_file,
+				None => continue,
+			};
+			let l_prev_size = Rotate::size(&self.task.path.join(&l_prev.name));
+			if self.task.verify_max_deviation <= 0.0 || l_prev_size == 0
+			{
+				continue;
+			}
+
+			// Deviates too much
+			let l_deviation = (l_size as f64 - l_prev_size as f64).abs() / l_prev_size as f64;
+			if l_deviation > self.task.verify_max_deviation
+			{
+				eprintln!("Error: {}.{} artifact '{}' deviates by {:.0}% in size from '{}' ({} vs {} bytes)!", self.cfg.name, self.name, l_file.name, l_deviation * 100.0, l_prev.name, l_size, l_prev_size);
+				return false;
+			}
+		}
+
+		// Done
+		println!("{}.{} verified.", self.cfg.name, self.name);
+		return true;
+	}
+
+	/// Wait
+	///
+	/// Polls until the task is locked by this process: true if locked, none on errors or when the timeout elapses.
+	fn wait(&self, a_timeout: u64) -> Option<bool>
+	{
+		// Hail
+		match a_timeout
+		{
+			0 => println!("{}.{} waiting (locked)...", self.cfg.name, self.name),
+			_ => println!("{}.{} waiting (locked, timeout: {}s)...", self.cfg.name, self.name, a_timeout),
+		}
+
+		// Get start
+		let l_start = Instant::now();
+
+		// Poll until locked
+		loop
+		{
+			// Timeout elapsed
+			if a_timeout > 0 && l_start.elapsed().as_secs() >= a_timeout
+			{
+				eprintln!("Error: {}.{} is still locked after {}s!", self.cfg.name, self.name, a_timeout);
+				return None;
+			}
+
+			// Sleep
+			thread::sleep(std::time::Duration::from_millis(LOCK_POLL));
+
+			// Lock
+			if State::lock(&self.task.path)?
+			{
+				return Some(true);
+			}
+		}
+	}
 }
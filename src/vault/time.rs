@@ -1,9 +1,23 @@
 // Use
-use chrono::{DateTime, TimeZone, Utc};
+use chrono::{DateTime, Duration, FixedOffset, Local, LocalResult, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono::format::{Fixed, Item, Parsed, StrftimeItems};
+use chrono_tz::Tz;
+use regex::escape;
 
 /// Format
 pub const FORMAT: &str = "%Y-%m-%d_%H-%M-%S%.9f";
 
+/// Zone enum
+#[derive(Clone, Copy, Debug)]
+pub enum Zone
+{
+	/// Local time zone of the system
+	Local,
+
+	/// IANA time zone
+	Tz(Tz),
+}
+
 /// Time struct
 pub struct Time;
 
@@ -13,30 +27,346 @@ impl Time
 	/// from string
 	pub fn from_string(a_str: &str) -> Option<DateTime<Utc>>
 	{
-		match Utc.datetime_from_str(a_str, FORMAT)
+		match NaiveDateTime::parse_from_str(a_str, FORMAT)
 		{
-			Ok(m_dt) => return Some(m_dt),
+			Ok(m_dt) => return Some(m_dt.and_utc()),
 			Err(_m_error) => return None,
 		}
 	}
 
+	/// Duration
+	///
+	/// Parses a duration like "400d" or "1w12h" (units: s, m, h, d, w, y).
+	pub fn duration(a_str: &str) -> Option<Duration>
+	{
+		let mut l_duration = Duration::zero();
+		let mut l_number = String::new();
+		for i_char in a_str.trim().chars()
+		{
+			// Digit
+			if i_char.is_ascii_digit()
+			{
+				l_number.push(i_char);
+				continue;
+			}
+
+			// Get number
+			let l_value = match l_number.parse::<i64>()
+			{
+				Ok(m_value) => m_value,
+				Err(_m_error) => return None,
+			};
+			l_number.clear();
+
+			// Get unit
+			let l_unit = match i_char
+			{
+				's' => Duration::try_seconds(l_value),
+				'm' => Duration::try_minutes(l_value),
+				'h' => Duration::try_hours(l_value),
+				'd' => Duration::try_days(l_value),
+				'w' => Duration::try_weeks(l_value),
+				'y' => l_value.checked_mul(365).and_then(Duration::try_days),
+				_ => return None,
+			};
+
+			// Add unit, durations out of range are invalid
+			l_duration = l_duration.checked_add(&l_unit?)?;
+		}
+
+		// Number without unit or nothing at all
+		if !l_number.is_empty() || l_duration.is_zero()
+		{
+			return None;
+		}
+
+		// Done
+		return Some(l_duration);
+	}
+
+	/// Format
+	///
+	/// Formats a time as seen in the given zone.
+	pub fn format(a_dt: &DateTime<Utc>, a_format: &str, a_zone: &Zone) -> String
+	{
+		return Time::zoned(a_dt, a_zone).format(a_format).to_string();
+	}
+
 	/// Now
 	pub fn now() -> DateTime<Utc>
 	{
 		return Utc::now();
 	}
 
+	/// Offset
+	///
+	/// Checks whether a strftime format contains an offset.
+	pub fn offset(a_format: &str) -> bool
+	{
+		return StrftimeItems::new(a_format).any(|a_item| matches!(a_item,
+			Item::Fixed(Fixed::TimezoneOffset) |
+			Item::Fixed(Fixed::TimezoneOffsetColon) |
+			Item::Fixed(Fixed::TimezoneOffsetDoubleColon) |
+			Item::Fixed(Fixed::TimezoneOffsetTripleColon) |
+			Item::Fixed(Fixed::TimezoneOffsetColonZ) |
+			Item::Fixed(Fixed::TimezoneOffsetZ)
+		));
+	}
+
+	/// Parse
+	///
+	/// Parses a string with a strftime format.
+	/// Missing parts (e.g. the time in "%Y%m%d") start at their beginning.
+	/// A format without an offset also accepts one ("%z") at the end of the string.
+	/// Without an offset in the string, it is read as a time of the given zone.
+	pub fn parse(a_str: &str, a_format: &str, a_zone: &Zone) -> Option<DateTime<Utc>>
+	{
+		// Parse into fields
+		let mut l_parsed = Parsed::new();
+		if chrono::format::parse(&mut l_parsed, a_str, StrftimeItems::new(a_format)).is_err()
+		{
+			// Parse with trailing offset
+			let l_format = format!("{}%z", a_format);
+			l_parsed = Parsed::new();
+			if Time::offset(a_format) || chrono::format::parse(&mut l_parsed, a_str, StrftimeItems::new(&l_format)).is_err()
+			{
+				return None;
+			}
+		}
+
+		// Complete missing date fields
+		if l_parsed.to_naive_date().is_err()
+		{
+			let _ = l_parsed.set_month(1);
+			let _ = l_parsed.set_day(1);
+		}
+
+		// Complete missing time fields
+		if l_parsed.to_naive_time().is_err()
+		{
+			let _ = l_parsed.set_hour(0);
+			let _ = l_parsed.set_minute(0);
+		}
+
+		// With offset
+		if let Ok(m_dt) = l_parsed.to_datetime()
+		{
+			return Some(m_dt.with_timezone(&Utc));
+		}
+
+		// Without offset
+		let l_date = match l_parsed.to_naive_date()
+		{
+			Ok(m_date) => m_date,
+			Err(_m_error) => return None,
+		};
+		let l_time = match l_parsed.to_naive_time()
+		{
+			Ok(m_time) => m_time,
+			Err(_m_error) => return None,
+		};
+		let l_naive = NaiveDateTime::new(l_date, l_time);
+		let l_local = match a_zone
+		{
+			Zone::Local => Local.from_local_datetime(&l_naive).map(|a_dt| a_dt.with_timezone(&Utc)),
+			Zone::Tz(m_tz) => m_tz.from_local_datetime(&l_naive).map(|a_dt| a_dt.with_timezone(&Utc)),
+		};
+		match l_local
+		{
+			LocalResult::Single(m_dt) => return Some(m_dt),
+			LocalResult::Ambiguous(m_dt, _) => return Some(m_dt),
+
+			// Skipped by a clock change, take the offset from before it
+			LocalResult::None =>
+			{
+				let l_offset = Time::zoned(&Utc.from_utc_datetime(&(l_naive - Duration::days(1))), a_zone).offset().fix();
+				return Some(Utc.from_utc_datetime(&(l_naive - Duration::seconds(l_offset.local_minus_utc() as i64))));
+			},
+		}
+	}
+
+	/// Regex
+	///
+	/// Converts a strftime format into a regular expression matching its output,
+	/// with an optional trailing offset for a format without one (See "parse").
+	/// Returns none for unsupported specifiers.
+	pub fn regex(a_format: &str) -> Option<String>
+	{
+		let mut l_regex = String::new();
+		let mut l_chars = a_format.chars().peekable();
+		while let Some(l_char) = l_chars.next()
+		{
+			// Literal
+			if l_char != '%'
+			{
+				l_regex.push_str(escape(l_char.to_string().as_str()).as_str());
+				continue;
+			}
+
+			// Get padding modifier
+			let mut l_unpadded = false;
+			if let Some(l_pad) = l_chars.next_if(|a_char| matches!(a_char, '-' | '_' | '0'))
+			{
+				l_unpadded = l_pad != '0';
+			}
+
+			// Get specifier, including colon and fraction modifiers
+			let mut l_key = String::new();
+			for l_next in l_chars.by_ref()
+			{
+				l_key.push(l_next);
+				if !matches!(l_next, ':' | '.' | '3' | '6' | '9')
+				{
+					break;
+				}
+			}
+
+			// Map specifier
+			let l_part = match (l_key.as_str(), l_unpadded)
+			{
+				("Y", _) | ("G", _) => "[+-]?[0-9]{4,}",
+				("C", false) | ("y", false) | ("g", false) | ("m", false) | ("d", false) | ("H", false) | ("I", false) | ("M", false) | ("S", false) | ("U", false) | ("W", false) | ("V", false) => "[0-9]{2}",
+				("C", true) | ("y", true) | ("g", true) | ("m", true) | ("d", true) | ("H", true) | ("I", true) | ("M", true) | ("S", true) | ("U", true) | ("W", true) | ("V", true) => " ?[0-9]{1,2}",
+				("e", _) | ("k", _) | ("l", _) => " ?[0-9]{1,2}",
+				("j", false) => "[0-9]{3}",
+				("j", true) => " {0,2}[0-9]{1,3}",
+				("u", _) | ("w", _) => "[0-9]",
+				("f", _) => "[0-9]{9}",
+				(".f", _) => "(?:\\.[0-9]+)?",
+				(".3f", _) => "\\.[0-9]{3}",
+				(".6f", _) => "\\.[0-9]{6}",
+				(".9f", _) => "\\.[0-9]{9}",
+				("3f", _) => "[0-9]{3}",
+				("6f", _) => "[0-9]{6}",
+				("9f", _) => "[0-9]{9}",
+				("s", _) => "-?[0-9]+",
+				("z", _) => "[+-][0-9]{4}",
+				(":z", _) => "[+-][0-9]{2}:[0-9]{2}",
+				("a", _) | ("b", _) | ("h", _) => "[A-Za-z]{3}",
+				("A", _) | ("B", _) => "[A-Za-z]+",
+				("p", _) => "(?:AM|PM)",
+				("P", _) => "(?:am|pm)",
+				("F", _) => "[+-]?[0-9]{4,}-[0-9]{2}-[0-9]{2}",
+				("T", _) | ("X", _) => "[0-9]{2}:[0-9]{2}:[0-9]{2}",
+				("R", _) => "[0-9]{2}:[0-9]{2}",
+				("D", _) | ("x", _) => "[0-9]{2}/[0-9]{2}/[0-9]{2}",
+				("%", _) => "%",
+				_ => return None,
+			};
+			l_regex.push_str(l_part);
+		}
+
+		// Optional offset
+		if !Time::offset(a_format)
+		{
+			l_regex.push_str("(?:[+-][0-9]{4})?");
+		}
+		return Some(l_regex);
+	}
+
 	/// To string
 	pub fn to_string(a_dt: &DateTime<Utc>) -> String
 	{
 		return a_dt.format(FORMAT).to_string();
 	}
 
+	/// Zone
+	///
+	/// Parses a time zone, either an IANA name (e.g. "Europe/Berlin") or "local".
+	pub fn zone(a_str: &str) -> Option<Zone>
+	{
+		if a_str.eq_ignore_ascii_case("local")
+		{
+			return Some(Zone::Local);
+		}
+		match a_str.parse::<Tz>()
+		{
+			Ok(m_tz) => return Some(Zone::Tz(m_tz)),
+			Err(_m_error) => return None,
+		}
+	}
+
+	/// Zoned
+	///
+	/// Converts a time into the given zone, keeping its offset.
+	pub fn zoned(a_dt: &DateTime<Utc>, a_zone: &Zone) -> DateTime<FixedOffset>
+	{
+		let l_offset = match a_zone
+		{
+			Zone::Local => a_dt.with_timezone(&Local).offset().fix(),
+			Zone::Tz(m_tz) => a_dt.with_timezone(m_tz).offset().fix(),
+		};
+		return a_dt.with_timezone(&l_offset);
+	}
+
 }
 
 /// Tests mod
 mod tests
 {
+	/// Duration
+	#[test]
+	fn duration()
+	{
+		use chrono::Duration;
+		use crate::vault::time::Time as Time;
+		assert_eq!(Time::duration("400d"), Some(Duration::days(400)));
+		assert_eq!(Time::duration("1w12h"), Some(Duration::days(7) + Duration::hours(12)));
+		assert_eq!(Time::duration("1y"), Some(Duration::days(365)));
+		assert_eq!(Time::duration("12"), None);
+		assert_eq!(Time::duration("3x"), None);
+		assert_eq!(Time::duration("99999999999y"), None);
+		assert_eq!(Time::duration("99999999999999999999s"), None);
+		assert_eq!(Time::duration("9223372036854775w"), None);
+		assert_eq!(Time::duration(""), None);
+	}
+
+	/// Parse
+	#[test]
+	fn parse()
+	{
+		use crate::vault::time::Time as Time;
+		for (i_str, i_format, i_expect) in
+		[
+			("2024-01-02_03-04-05.000000006", crate::vault::time::FORMAT, "2024-01-02_03-04-05.000000006"),
+			("20240102", "%Y%m%d", "2024-01-02_00-00-00.000000000"),
+			("2024-05", "%Y-%m", "2024-05-01_00-00-00.000000000"),
+			("2024-01-02T03:04:05+0200", "%Y-%m-%dT%H:%M:%S%z", "2024-01-02_01-04-05.000000000"),
+			("20240102+0200", "%Y%m%d", "2024-01-01_22-00-00.000000000"),
+		]
+		{
+			match Time::parse(i_str, i_format, &crate::vault::time::Zone::Tz(chrono_tz::UTC))
+			{
+				Some(m_dt) => assert_eq!(i_expect, Time::to_string(&m_dt).as_str()),
+				None => assert!(false),
+			};
+		}
+	}
+
+	/// Regex
+	#[test]
+	fn regex()
+	{
+		use crate::vault::time::Time as Time;
+		for (i_format, i_str) in
+		[
+			(crate::vault::time::FORMAT, "2024-01-02_03-04-05.000000006"),
+			("%Y%m%d", "20240102"),
+			("%F_%H%M", "2024-01-02_0304"),
+			("%Y-%m-%dT%H:%M:%S%:z", "2024-01-02T03:04:05+02:00"),
+			("%Y%m%d", "20240102+0100"),
+		]
+		{
+			match Time::regex(i_format)
+			{
+				Some(m_regex) => assert!(regex::Regex::new(format!("^{}$", m_regex).as_str()).unwrap().is_match(i_str)),
+				None => assert!(false),
+			};
+		}
+		assert!(Time::regex("%Q").is_none());
+		assert!(!regex::Regex::new(format!("^{}$", Time::regex("%Y%m%d%z").unwrap()).as_str()).unwrap().is_match("20240102"));
+	}
+
 	/// Smoke
 	#[test]
 	fn smoke()
@@ -52,4 +382,36 @@ mod tests
 			};
 		}
 	}
+
+	/// Zone
+	#[test]
+	fn zone()
+	{
+		use crate::vault::time::Time as Time;
+		let l_zone = match Time::zone("Europe/Berlin")
+		{
+			Some(m_zone) => m_zone,
+			None => return assert!(false),
+		};
+		assert!(Time::zone("Mars/Olympus").is_none());
+
+		// Summer and winter time read back with their offsets
+		for (i_str, i_expect, i_stamp) in
+		[
+			("2024-07-01_00-30", "2024-06-30_22-30-00.000000000", "2024-07-01_00-30+0200"),
+			("2024-01-01_00-30", "2023-12-31_23-30-00.000000000", "2024-01-01_00-30+0100"),
+			("2024-03-31_02-30", "2024-03-31_01-30-00.000000000", "2024-03-31_03-30+0200"),
+		]
+		{
+			match Time::parse(i_str, "%Y-%m-%d_%H-%M", &l_zone)
+			{
+				Some(m_dt) =>
+				{
+					assert_eq!(i_expect, Time::to_string(&m_dt).as_str());
+					assert_eq!(i_stamp, Time::format(&m_dt, "%Y-%m-%d_%H-%M%z", &l_zone).as_str());
+				},
+				None => assert!(false),
+			};
+		}
+	}
 }
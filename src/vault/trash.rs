@@ -0,0 +1,459 @@
+// Use
+use chrono::{DateTime, TimeZone, Utc};
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::vec::Vec;
+use crate::vault::journal::{Journal, JournalEntry};
+use crate::vault::rotate::{ARCHIVE_EXTENSION, ARCHIVE_INDEX, Rotate};
+use crate::vault::task::Task;
+use crate::vault::time::Time;
+
+// TrashFile struct
+#[derive(Clone, Debug)]
+pub struct TrashFile
+{
+	/// Index of an archive, empty for moved files
+	pub index: String,
+
+	/// Name
+	pub name: String,
+
+	/// Size
+	pub size: u64,
+
+	/// Time
+	pub time: DateTime<Utc>,
+}
+
+// Trash struct
+pub struct Trash
+{
+	/// Path
+	path: PathBuf,
+
+	/// Task
+	task: Task,
+}
+
+// Trash impl
+impl Trash
+{
+	/// Empty
+	pub fn empty(&self) -> bool
+	{
+		// Get files
+		let l_files = match self.list()
+		{
+			Some(m_files) => m_files,
+			None => return false,
+		};
+
+		// Delete all files
+		for i_file in &l_files
+		{
+			if !self.file_delete(i_file)
+			{
+				return false;
+			}
+		}
+
+		// Done
+		println!("{}.{} trash emptied ({} {}).", self.task.cfg.name, self.task.name, l_files.len(), Rotate::word(l_files.len()));
+		return true;
+	}
+
+	/// Expire
+	///
+	/// Deletes files exceeding the maximum age or size of the moved directory, oldest first.
+	pub fn expire(&self) -> bool
+	{
+		// No expiry
+		if self.task.task.moved_max_age.is_empty() && self.task.task.moved_max_size.is_empty()
+		{
+			return true;
+		}
+
+		// Moved directory does not exist
+		if !self.path.exists()
+		{
+			return true;
+		}
+
+		// Get oldest time to keep
+		let l_oldest = match self.task.limit_age("moved max age", &self.task.task.moved_max_age)
+		{
+			Some(m_oldest) => m_oldest,
+			None => return false,
+		};
+
+		// Get max size
+		let l_max_size = match self.task.limit_size("moved max size", &self.task.task.moved_max_size)
+		{
+			Some(m_size) => m_size,
+			None => return false,
+		};
+
+		// Get files, oldest first
+		let l_files = match self.list()
+		{
+			Some(m_files) => m_files,
+			None => return false,
+		};
+
+		// Get total size
+		let mut l_total: u64 = l_files.iter().map(|a_file| a_file.size).sum();
+
+		// Iterate over files
+		let mut l_count: usize = 0;
+		for i_file in &l_files
+		{
+			// Too old
+			let l_age = match l_oldest
+			{
+				Some(m_oldest) => i_file.time < m_oldest,
+				None => false,
+			};
+
+			// Too large
+			let l_size = match l_max_size
+			{
+				Some(m_max_size) => l_total > m_max_size,
+				None => false,
+			};
+
+			// Keep the rest
+			if !l_age && !l_size
+			{
+				break;
+			}
+
+			// Delete file
+			if !self.file_delete(i_file)
+			{
+				return false;
+			}
+			l_total -= i_file.size;
+			l_count += 1;
+		}
+
+		// Display expiry status
+		if l_count > 0
+		{
+			println!("{}.{} trash expired {} {}.", self.task.cfg.name, self.task.name, l_count, Rotate::word(l_count));
+		}
+
+		// Done
+		return true;
+	}
+
+	/// File delete
+	///
+	/// Deletes a file of the trash, together with the index of an archive.
+	fn file_delete(&self, a_file: &TrashFile) -> bool
+	{
+		for i_name in [&a_file.name, &a_file.index].into_iter().filter(|a_name| !a_name.is_empty())
+		{
+			// Index already gone
+			let l_path = self.path.join(i_name);
+			if *i_name == a_file.index && fs::symlink_metadata(&l_path).is_err()
+			{
+				continue;
+			}
+
+			// Delete
+			if let Err(m_error) = Rotate::remove(&l_path)
+			{
+				eprintln!("Error: {}.{} failed to delete file '{}'!\n{}", self.task.cfg.name, self.task.name, l_path.display(), m_error.to_string());
+				return false;
+			}
+		}
+		return true;
+	}
+
+	/// List
+	///
+	/// Lists the files this task moved or archived into the move target, as recorded in its journal,
+	/// oldest first. Anything else in there is left alone, as it may belong to someone else.
+	/// The time of a file is its change time (creation time, where not available),
+	/// which is the time it has been moved.
+	fn list(&self) -> Option<Vec<TrashFile>>
+	{
+		// Create list
+		let mut l_list = Vec::<TrashFile>::new();
+
+		// Moved directory does not exist
+		if !self.path.exists()
+		{
+			return Some(l_list);
+		}
+
+		// Load journal
+		let l_journal = Journal::load(&self.task.task.path)?;
+
+		// Get files moved or archived into the move target
+		let l_files = l_journal.rotation
+			.iter()
+			.filter(|a_entry| a_entry.strategy == "archive" || a_entry.strategy == "move")
+			.filter(|a_entry| Path::new(&a_entry.target).parent() == Some(self.path.as_path()))
+			.filter_map(|a_entry| Some((Path::new(&a_entry.target).file_name()?.to_str()?.to_string(), a_entry.strategy == "archive")))
+			.collect::<BTreeSet<(String, bool)>>()
+		;
+
+		// Iterate over files
+		for (i_name, i_archive) in l_files
+		{
+			// Get metadata, files restored or deleted meanwhile are gone
+			let l_meta = match fs::symlink_metadata(self.path.join(&i_name))
+			{
+				Ok(m_meta) => m_meta,
+				Err(_m_error) => continue,
+			};
+
+			// Get index of an archive
+			let l_index = match i_name.strip_suffix(ARCHIVE_EXTENSION)
+			{
+				Some(m_base) if i_archive => m_base.to_string() + ARCHIVE_INDEX,
+				_ => String::new(),
+			};
+
+			// Get time
+			#[cfg(unix)]
+			let l_time =
+			{
+				use std::os::unix::fs::MetadataExt;
+				Utc.timestamp_opt(l_meta.ctime(), l_meta.ctime_nsec() as u32).single()
+			};
+			#[cfg(not(unix))]
+			let l_time = match l_meta.created()
+			{
+				Ok(m_time) => Some(DateTime::<Utc>::from(m_time)),
+				Err(_m_error) => None,
+			};
+
+			// Add to list
+			l_list.push(TrashFile
+			{
+				index: l_index,
+				name: i_name,
+				size: 0,
+				time: l_time.unwrap_or_else(Time::now),
+			});
+		}
+
+		// Sort by time, oldest first
+		l_list.sort_by(|a_left, a_right| a_left.time.cmp(&a_right.time).then(a_left.name.cmp(&a_right.name)));
+
+		// Get sizes newest first, so files hard linked between snapshots
+		// count for the newest one, which keeps them after older ones expire
+		let mut l_seen = HashSet::<(u64, u64)>::new();
+		for i_file in l_list.iter_mut().rev()
+		{
+			i_file.size = Rotate::size_once(&self.path.join(&i_file.name), &mut l_seen);
+			if !i_file.index.is_empty()
+			{
+				i_file.size += Rotate::size_once(&self.path.join(&i_file.index), &mut l_seen);
+			}
+		}
+
+		// Done
+		return Some(l_list);
+	}
+
+	/// New
+	pub fn new(a_task: &Task) -> Trash
+	{
+		return Trash
+		{
+			path: a_task.target(),
+			task: a_task.clone(),
+		};
+	}
+
+	/// Restore
+	///
+	/// Moves a file from the moved directory back into the path of the task.
+	pub fn restore(&self, a_file: &str) -> bool
+	{
+		// Get files
+		let l_files = match self.list()
+		{
+			Some(m_files) => m_files,
+			None => return false,
+		};
+
+		// File not moved there by this task
+		if !l_files.iter().any(|a_file_trash| a_file_trash.name == a_file)
+		{
+			eprintln!("Error: {}.{} file '{}' is not in the trash!", self.task.cfg.name, self.task.name, a_file);
+			return false;
+		}
+
+		// Move back
+		let l_source = self.path.join(a_file);
+		let l_target = PathBuf::new().join(self.task.task.path.clone()).join(a_file);
+		return self.restore_path(&l_source, &l_target, a_file);
+	}
+
+	/// Restore path
+	fn restore_path(&self, a_source: &Path, a_target: &Path, a_file: &str) -> bool
+	{
+		// Source does not exist
+		if !a_source.exists()
+		{
+			eprintln!("Error: {}.{} file '{}' does not exist!", self.task.cfg.name, self.task.name, a_source.display());
+			return false;
+		}
+
+		// Target already exists
+		if a_target.exists()
+		{
+			eprintln!("Error: {}.{} file '{}' already exists!", self.task.cfg.name, self.task.name, a_target.display());
+			return false;
+		}
+
+		// Move back
+		match Rotate::transfer(a_source, a_target)
+		{
+			Ok(_) =>
+			{
+				println!("{}.{} restored '{}'.", self.task.cfg.name, self.task.name, a_file);
+				return true;
+			},
+			Err(m_error) =>
+			{
+				eprintln!("Error: {}.{} failed to restore file '{}'!\n{}", self.task.cfg.name, self.task.name, a_source.display(), m_error.to_string());
+				return false;
+			},
+		}
+	}
+
+	/// Undo
+	///
+	/// Moves the files of a rotation run back, as recorded in the journal,
+	/// and verifies their checksums.
+	pub fn undo(&self, a_run: &str) -> bool
+	{
+		// Load journal
+		let l_journal = match Journal::load(&self.task.task.path)
+		{
+			Some(m_journal) => m_journal,
+			None => return false,
+		};
+
+		// Get moved files of the run, not undone yet
+		let l_undone = l_journal.rotation
+			.iter()
+			.filter(|a_entry| a_entry.run == a_run && a_entry.strategy == "undo")
+			.map(|a_entry| a_entry.file.clone())
+			.collect::<BTreeSet<String>>()
+		;
+		let l_entries = l_journal.rotation
+			.into_iter()
+			.filter(|a_entry| a_entry.run == a_run && a_entry.strategy == "move" && !l_undone.contains(&a_entry.file))
+			.collect::<Vec<JournalEntry>>()
+		;
+
+		// Nothing to undo
+		if l_entries.is_empty()
+		{
+			eprintln!("Error: {}.{} has no moved files to restore in rotation run '{}'!", self.task.cfg.name, self.task.name, a_run);
+			return false;
+		}
+
+		// Iterate over entries
+		let mut l_status = true;
+		let mut l_done = Vec::<JournalEntry>::new();
+		for i_entry in l_entries
+		{
+			// Move back
+			let l_target = PathBuf::new().join(self.task.task.path.clone()).join(&i_entry.file);
+			if !self.restore_path(Path::new(&i_entry.target), &l_target, &i_entry.file)
+			{
+				l_status = false;
+				continue;
+			}
+
+			// Verify checksum
+			match Journal::checksum(&l_target)
+			{
+				Ok(m_checksum) if m_checksum == i_entry.checksum => {},
+				_ =>
+				{
+					eprintln!("Error: {}.{} checksum of restored file '{}' differs from the journal!", self.task.cfg.name, self.task.name, i_entry.file);
+					l_status = false;
+				},
+			}
+
+			// Record undo
+			l_done.push(JournalEntry
+			{
+				strategy: String::from("undo"),
+				time: Time::to_string(&Time::now()),
+				..i_entry
+			});
+		}
+
+		// Journal
+		if !Journal::append(&self.task.task.path, &l_done)
+		{
+			return false;
+		}
+
+		// Done
+		println!("{}.{} undid rotation run '{}' ({} {}).", self.task.cfg.name, self.task.name, a_run, l_done.len(), Rotate::word(l_done.len()));
+		return l_status;
+	}
+}
+
+/// Tests mod
+mod tests
+{
+	/// Empty
+	#[test]
+	fn empty()
+	{
+		use std::fs;
+		use crate::vault::config::{Config, ConfigTask};
+		use crate::vault::journal::{Journal, JournalEntry};
+		use crate::vault::rotate::Rotate as Rotate;
+		use crate::vault::task::Task as Task;
+		use crate::vault::trash::Trash as Trash;
+
+		// Task with a shared move target
+		let l_dir = std::env::temp_dir().join(format!("vault-test-trash-{}", std::process::id()));
+		let _ = Rotate::remove(&l_dir);
+		fs::create_dir_all(l_dir.join("bk")).unwrap();
+		fs::create_dir_all(l_dir.join("shared")).unwrap();
+		let mut l_task = Task
+		{
+			cfg: Config::default(),
+			name: String::from("test"),
+			task: ConfigTask::default(),
+		};
+		l_task.task.path = l_dir.join("bk");
+		l_task.task.rotate_move_target = l_dir.join("shared").to_string_lossy().to_string();
+
+		// Moved file, archive with index and a file of someone else
+		for i_name in ["a.txt", "b.tar.zst", "b.tar.zst.index.toml", "foreign.txt"]
+		{
+			fs::write(l_dir.join("shared").join(i_name), i_name).unwrap();
+		}
+		let l_entries = [("a.txt", "move"), ("b.tar.zst", "archive")].map(|(a_name, a_strategy)| JournalEntry
+		{
+			strategy: a_strategy.to_string(),
+			target: l_dir.join("shared").join(a_name).to_string_lossy().to_string(),
+			..JournalEntry::default()
+		});
+		assert!(Journal::append(&l_task.task.path, &l_entries));
+
+		// Only journaled files can be restored
+		let l_trash = Trash::new(&l_task);
+		assert!(!l_trash.restore("foreign.txt"));
+
+		// Only journaled files are deleted
+		assert!(l_trash.empty());
+		assert_eq!(fs::read_dir(l_dir.join("shared")).unwrap().map(|a_item| a_item.unwrap().file_name()).collect::<Vec<_>>(), ["foreign.txt"]);
+		Rotate::remove(&l_dir).unwrap();
+	}
+}